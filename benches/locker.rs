@@ -1,5 +1,8 @@
 use criterion::{criterion_group, criterion_main, Criterion};
-use sdw::{locker::Locker, model::Channel};
+use sdw::{
+    locker::Locker,
+    model::{Channel, RecordKind},
+};
 use time::OffsetDateTime;
 
 pub fn locker_open(c: &mut Criterion) {
@@ -15,7 +18,10 @@ pub fn iterator_filter(c: &mut Criterion) {
 
     c.bench_function("iterator_filter", |b| {
         b.iter(|| {
-            locker.iter().filter(|(k, _)| k.0 == "Ping").count();
+            locker
+                .iter()
+                .filter(|(k, _)| k.0 == RecordKind::Ping)
+                .count();
         })
     });
 }
@@ -29,14 +35,18 @@ pub fn range_filter(c: &mut Criterion) {
                 .index()
                 .range(
                     (
-                        "Ping".to_string(),
+                        RecordKind::Ping,
                         OffsetDateTime::UNIX_EPOCH,
                         Channel::Port,
+                        0,
+                        0,
                     )
                         ..(
-                            "Ping".to_string(),
+                            RecordKind::Ping,
                             OffsetDateTime::now_utc(),
                             Channel::Starboard,
+                            0,
+                            0,
                         ),
                 )
                 .count();
@@ -44,6 +54,23 @@ pub fn range_filter(c: &mut Criterion) {
     });
 }
 
+pub fn query_filter(c: &mut Criterion) {
+    let locker = Locker::open("assets/HE501").unwrap();
+
+    c.bench_function("query_filter", |b| {
+        b.iter(|| {
+            locker
+                .query()
+                .kind(RecordKind::Ping)
+                .start(OffsetDateTime::UNIX_EPOCH)
+                .end(OffsetDateTime::now_utc())
+                .channels([Channel::Port, Channel::Starboard])
+                .keys()
+                .count();
+        })
+    });
+}
+
 pub fn get_locker(c: &mut Criterion) {
     let locker = Locker::open("assets/HE501").unwrap();
 
@@ -67,6 +94,7 @@ criterion_group!(
     locker_open,
     iterator_filter,
     range_filter,
+    query_filter,
     get_locker
 );
 criterion_main!(benches);
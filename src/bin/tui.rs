@@ -133,27 +133,23 @@ fn ui<T, B: Backend>(f: &mut Frame<B>, app: &mut App<T>) {
         .iter()
         .enumerate()
         .map(|(i, rec)| {
-            let datatype = match rec {
-                SonarDataRecord::Ping(_) => "Ping".to_string(),
-                SonarDataRecord::Course(_) => "Course".to_string(),
-                SonarDataRecord::Orientation(_) => "Orientation".to_string(),
-                SonarDataRecord::Position(_) => "Position".to_string(),
-                SonarDataRecord::Unknown => "Unknown".to_string(),
-            };
+            let datatype = rec.kind();
 
             let timestamp = match rec {
                 SonarDataRecord::Ping(rec) => rec.timestamp.format(&format).unwrap(),
                 SonarDataRecord::Course(rec) => rec.timestamp.format(&format).unwrap(),
                 SonarDataRecord::Orientation(rec) => rec.timestamp.format(&format).unwrap(),
                 SonarDataRecord::Position(rec) => rec.timestamp.format(&format).unwrap(),
+                SonarDataRecord::Bathymetry(rec) => rec.timestamp.format(&format).unwrap(),
                 SonarDataRecord::Unknown => "".to_string(),
             };
 
             let channel = match rec {
-                SonarDataRecord::Ping(rec) => format!("{:?}", rec.channel),
+                SonarDataRecord::Ping(rec) => format!("{:?} {}", rec.channel, rec.subsystem),
                 SonarDataRecord::Course(_) => "".to_string(),
                 SonarDataRecord::Orientation(_) => "".to_string(),
                 SonarDataRecord::Position(_) => "".to_string(),
+                SonarDataRecord::Bathymetry(rec) => format!("{:?}", rec.channel),
                 SonarDataRecord::Unknown => "".to_string(),
             };
 
@@ -18,7 +18,7 @@ pub fn avro(
     output: &std::path::PathBuf,
     compress: &bool,
 ) -> std::io::Result<()> {
-    let raw_schema = r#"{"type": "record","namespace": "sdw","name": "ping","fields": [{"name": "source", "type": "string"},{"name": "timestamp", "type": "long"},{"name": "frequency", "type" : "double"},{"name": "sampling_interval", "type" : "double"},{"name": "channel", "type": "enum", "symbols":["Port","Starboard","Other"],"default":"Other"},{"name": "data", "type":"array","items": "int","default":[]}]}"#;
+    let raw_schema = r#"{"type": "record","namespace": "sdw","name": "ping","fields": [{"name": "source", "type": "string"},{"name": "timestamp", "type": "long"},{"name": "frequency", "type" : "double"},{"name": "sampling_interval", "type" : "double"},{"name": "channel", "type": "enum", "symbols":["Port","Starboard","Other"],"default":"Other"},{"name": "subsystem", "type": "int", "default": 0},{"name": "data", "type":"array","items": "int","default":[]}]}"#;
     let ping_schema = Schema::parse_str(raw_schema).unwrap();
 
     let f = std::fs::File::open(path)?;
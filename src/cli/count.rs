@@ -1,10 +1,10 @@
 //! Count SonarDataRecords in a given file
-use crate::model::SonarDataRecord;
+use crate::model::{RecordKind, SonarDataRecord};
 use crate::parser::jsf;
 use binrw::io::BufReader;
 use std::io::{stdout, Write};
 
-fn count_records<T>(file: jsf::File<T>) -> std::collections::HashMap<String, i64>
+fn count_records<T>(file: jsf::File<T>) -> std::collections::HashMap<RecordKind, i64>
 where
     T: std::io::Seek + std::io::Read,
 {
@@ -12,14 +12,7 @@ where
 
     file.fold(&mut msg_counts, |counts, msg| {
         let rec = SonarDataRecord::from(msg.unwrap());
-        let mt = match rec {
-            SonarDataRecord::Ping(_) => "Ping".to_string(),
-            SonarDataRecord::Position(_) => "Position".to_string(),
-            SonarDataRecord::Orientation(_) => "Orientation".to_string(),
-            SonarDataRecord::Course(_) => "Course".to_string(),
-            SonarDataRecord::Unknown => "Unknown".to_string(),
-        };
-        let num = counts.entry(mt).or_insert(0);
+        let num = counts.entry(rec.kind()).or_insert(0);
         *num += 1;
         counts
     });
@@ -1,9 +1,11 @@
 //! Print info about a sonar file
 
-use crate::model::{Channel, Course, Orientation, Ping, Position, SonarDataRecord};
+use crate::model::{
+    Bathymetry, Channel, Course, Orientation, Ping, Position, SonarDataRecord,
+};
 use crate::parser::jsf;
 use binrw::io::BufReader;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use time::OffsetDateTime;
 
 /// Print info about a sonar file
@@ -11,23 +13,25 @@ pub fn info<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<()> {
     let f = std::fs::File::open(path.as_ref())?;
     let reader = BufReader::new(f);
     let format = "JSF".to_string();
-    let jsf = jsf::File::new(reader);
+    let mut jsf = jsf::File::new(reader);
 
     let mut port_channel_count = 0;
     let mut starboard_channel_count = 0;
     let mut other_channel_count = 0;
+    let mut subsystem_counts = BTreeMap::new();
     let mut data_lengths = HashSet::new();
     let mut sampling_intervals = Vec::new();
     let mut frequencies = Vec::new();
     let mut start_date = OffsetDateTime::now_utc();
     let mut end_date = OffsetDateTime::UNIX_EPOCH;
 
-    for msg in jsf {
+    for msg in jsf.by_ref() {
         let rec = SonarDataRecord::from(msg.unwrap());
         match rec {
             SonarDataRecord::Ping(Ping {
                 timestamp,
                 channel,
+                subsystem,
                 data,
                 sampling_interval,
 		frequency,		
@@ -38,6 +42,7 @@ pub fn info<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<()> {
                     Channel::Starboard => starboard_channel_count += 1,
                     Channel::Other => other_channel_count += 1,
                 }
+                *subsystem_counts.entry(subsystem).or_insert(0) += 1;
 
                 if timestamp < start_date {
                     start_date = timestamp;
@@ -86,6 +91,13 @@ pub fn info<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<()> {
                     end_date = timestamp;
                 }
             }
+            SonarDataRecord::Bathymetry(Bathymetry { timestamp, .. }) => {
+                if timestamp < start_date {
+                    start_date = timestamp;
+                } else if timestamp > end_date {
+                    end_date = timestamp;
+                }
+            }
             SonarDataRecord::Unknown => {}
         };
     }
@@ -100,6 +112,10 @@ pub fn info<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<()> {
         starboard_channel_count
     );
     println!("Number of other channel pings: {}", other_channel_count);
+    println!("Number of pings per subsystem:");
+    for (subsystem, count) in &subsystem_counts {
+        println!("\t{}: {}", subsystem, count);
+    }
     println!("Unique lengths of pings:");
     for length in &data_lengths {
         println!("\t{}", length);
@@ -119,5 +135,21 @@ pub fn info<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<()> {
         println!("\t{} kHz", f/1.0e3);
     }
 
+    println!("Clock discontinuities: {}", jsf.discontinuities().len());
+    for d in jsf.discontinuities() {
+        println!(
+            "\t{}/{} at byte {}: {} -> {}",
+            d.subsystem, d.channel, d.offset, d.previous, d.timestamp
+        );
+    }
+
+    println!("Invalid timestamps: {}", jsf.invalid_timestamps().len());
+    for t in jsf.invalid_timestamps() {
+        println!(
+            "\t{}/{} at byte {}: {}",
+            t.subsystem, t.channel, t.offset, t.error
+        );
+    }
+
     Ok(())
 }
@@ -5,14 +5,7 @@ use binrw::io::BufReader;
 use std::io::{stdout, Write};
 
 fn write_record<T, W: Write>(mut writer: W, rec: SonarDataRecord<T>) -> std::io::Result<()> {
-    let datatype = match rec {
-        SonarDataRecord::Ping(_) => "Ping".to_string(),
-        SonarDataRecord::Position(_) => "Position".to_string(),
-        SonarDataRecord::Orientation(_) => "Orientation".to_string(),
-        SonarDataRecord::Course(_) => "Course".to_string(),
-        SonarDataRecord::Unknown => "Unknown".to_string(),
-    };
-    writeln!(writer, "{}", datatype)?;
+    writeln!(writer, "{}", rec.kind())?;
     Ok(())
 }
 
@@ -0,0 +1,278 @@
+//! The on-disk locker index
+//!
+//! The index of a locker is cached in a sidecar file inside the locker
+//! directory, so that reopening a locker only parses files that are new
+//! or have changed since the index was written. Files are revalidated
+//! by their size and modification time.
+use super::{FileInfo, LockerKey, LockerValue};
+use crate::model::{Channel, RecordKind};
+use crate::parser::Format;
+use binrw::io::BufReader;
+use binrw::{binrw, BinRead, BinResult, BinWrite};
+use std::collections::{BTreeMap, HashMap};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime};
+use time::OffsetDateTime;
+
+/// The name of the index file within the locker directory
+pub(super) const INDEX_FILE_NAME: &str = ".sdw-index";
+
+/// The number of index files written so far by this process
+static SAVED: AtomicUsize = AtomicUsize::new(0);
+
+/// Return true if `path` is the index file, or a temporary copy
+pub(super) fn is_index_file(path: &Path) -> bool {
+    match path.file_name().and_then(|name| name.to_str()) {
+        Some(name) => name.starts_with(INDEX_FILE_NAME),
+        None => false,
+    }
+}
+
+/// The version of the index file layout
+///
+/// This must be incremented whenever the layout or the meaning of
+/// the stored keys changes, or a parser yields different records for
+/// the same file, so that stale indices are rebuilt.
+const INDEX_VERSION: u16 = 7;
+
+/// The metadata and index entries of a file read from the cache
+pub(super) type CachedFile = (FileInfo, Vec<(LockerKey, u64, u16)>);
+
+#[binrw]
+#[brw(little, magic = b"SDWL")]
+struct IndexFile {
+    #[br(assert(version == INDEX_VERSION))]
+    version: u16,
+    /// Whether companion records were indexed
+    companion_records: u8,
+    #[bw(calc = files.len() as u32)]
+    n_files: u32,
+    #[br(count = n_files)]
+    files: Vec<FileRecord>,
+}
+
+#[binrw]
+#[brw(little)]
+struct FileRecord {
+    #[bw(calc = path.len() as u32)]
+    path_len: u32,
+    #[br(count = path_len)]
+    path: Vec<u8>,
+    format: u8,
+    size: u64,
+    modified_secs: u64,
+    modified_nanos: u32,
+    #[bw(calc = entries.len() as u32)]
+    n_entries: u32,
+    #[br(count = n_entries)]
+    entries: Vec<EntryRecord>,
+}
+
+#[binrw]
+#[brw(little)]
+struct EntryRecord {
+    kind: u8,
+    timestamp: i128,
+    channel: u8,
+    subsystem: u8,
+    offset: u64,
+    position: u16,
+}
+
+fn invalid_data(msg: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, msg)
+}
+
+fn format_to_u8(format: Format) -> u8 {
+    match format {
+        Format::Jsf => 0,
+        Format::Xtf => 1,
+        Format::Imagenex81b => 2,
+    }
+}
+
+fn format_from_u8(format: u8) -> std::io::Result<Format> {
+    match format {
+        0 => Ok(Format::Jsf),
+        1 => Ok(Format::Xtf),
+        2 => Ok(Format::Imagenex81b),
+        _ => Err(invalid_data("Unknown format in locker index")),
+    }
+}
+
+fn kind_to_u8(kind: RecordKind) -> u8 {
+    match kind {
+        RecordKind::Ping => 0,
+        RecordKind::Position => 1,
+        RecordKind::Orientation => 2,
+        RecordKind::Course => 3,
+        RecordKind::Bathymetry => 4,
+        RecordKind::Unknown => 5,
+    }
+}
+
+fn kind_from_u8(kind: u8) -> std::io::Result<RecordKind> {
+    match kind {
+        0 => Ok(RecordKind::Ping),
+        1 => Ok(RecordKind::Position),
+        2 => Ok(RecordKind::Orientation),
+        3 => Ok(RecordKind::Course),
+        4 => Ok(RecordKind::Bathymetry),
+        5 => Ok(RecordKind::Unknown),
+        _ => Err(invalid_data("Unknown record kind in locker index")),
+    }
+}
+
+fn channel_to_u8(channel: Channel) -> u8 {
+    match channel {
+        Channel::Port => 0,
+        Channel::Starboard => 1,
+        Channel::Other => 2,
+    }
+}
+
+fn channel_from_u8(channel: u8) -> std::io::Result<Channel> {
+    match channel {
+        0 => Ok(Channel::Port),
+        1 => Ok(Channel::Starboard),
+        2 => Ok(Channel::Other),
+        _ => Err(invalid_data("Unknown channel in locker index")),
+    }
+}
+
+impl EntryRecord {
+    fn new(key: &LockerKey, offset: u64, position: u16) -> Self {
+        EntryRecord {
+            kind: kind_to_u8(key.0),
+            timestamp: key.1.unix_timestamp_nanos(),
+            channel: channel_to_u8(key.2),
+            subsystem: key.3,
+            offset,
+            position,
+        }
+    }
+
+    fn entry(self) -> std::io::Result<(LockerKey, u64, u16)> {
+        let kind = kind_from_u8(self.kind)?;
+        let timestamp = OffsetDateTime::from_unix_timestamp_nanos(self.timestamp)
+            .map_err(|_| invalid_data("Invalid timestamp in locker index"))?;
+        let channel = channel_from_u8(self.channel)?;
+        // Sequence numbers are assigned when the index is assembled
+        Ok((
+            (kind, timestamp, channel, self.subsystem, 0),
+            self.offset,
+            self.position,
+        ))
+    }
+}
+
+/// Load the cached index of the locker at `root`
+///
+/// The returned paths are joined onto `root`.
+///
+/// # Errors
+///
+/// This function returns an error if the index file does not exist, was
+/// written with a different layout version or with a different setting of
+/// `companion_records`, or is corrupt.
+pub(super) fn load(
+    root: &Path,
+    companion_records: bool,
+) -> BinResult<BTreeMap<PathBuf, CachedFile>> {
+    let mut reader = BufReader::new(std::fs::File::open(root.join(INDEX_FILE_NAME))?);
+    let index_file = IndexFile::read(&mut reader)?;
+    if index_file.companion_records != u8::from(companion_records) {
+        return Err(invalid_data("Locker index built with different options").into());
+    }
+
+    let mut files = BTreeMap::new();
+    for file in index_file.files {
+        let path = String::from_utf8(file.path).map_err(|_| invalid_data("Invalid path"))?;
+        let info = FileInfo {
+            format: format_from_u8(file.format)?,
+            size: file.size,
+            modified: SystemTime::UNIX_EPOCH
+                + Duration::new(file.modified_secs, file.modified_nanos),
+        };
+        let entries = file
+            .entries
+            .into_iter()
+            .map(EntryRecord::entry)
+            .collect::<std::io::Result<Vec<_>>>()?;
+        files.insert(root.join(path), (info, entries));
+    }
+
+    Ok(files)
+}
+
+/// Save the index of the locker at `root`
+///
+/// The index is written to a temporary file which then replaces the
+/// existing index, so an interrupted write never leaves a corrupt index.
+/// Files whose paths are not valid UTF-8 are left out and will be
+/// re-indexed on the next open.
+pub(super) fn save(
+    root: &Path,
+    companion_records: bool,
+    files: &BTreeMap<PathBuf, FileInfo>,
+    index: &BTreeMap<LockerKey, LockerValue>,
+) -> BinResult<()> {
+    let mut entries: HashMap<&Path, Vec<EntryRecord>> = HashMap::new();
+    for (key, (path, offset, position)) in index {
+        entries
+            .entry(path.as_path())
+            .or_default()
+            .push(EntryRecord::new(key, *offset, *position));
+    }
+
+    let mut records = Vec::with_capacity(files.len());
+    for (path, info) in files {
+        let relative = match path.strip_prefix(root).ok().and_then(Path::to_str) {
+            Some(relative) => relative,
+            None => continue,
+        };
+        let modified = info
+            .modified
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_err(|_| invalid_data("Modification time before the epoch"))?;
+        records.push(FileRecord {
+            path: relative.as_bytes().to_vec(),
+            format: format_to_u8(info.format),
+            size: info.size,
+            modified_secs: modified.as_secs(),
+            modified_nanos: modified.subsec_nanos(),
+            entries: entries.remove(path.as_path()).unwrap_or_default(),
+        });
+    }
+
+    let index_file = IndexFile {
+        version: INDEX_VERSION,
+        companion_records: u8::from(companion_records),
+        files: records,
+    };
+
+    // Lockers opened at the same time each write their own copy
+    let tmp = root.join(format!(
+        "{}.{}.{}.tmp",
+        INDEX_FILE_NAME,
+        std::process::id(),
+        SAVED.fetch_add(1, Ordering::Relaxed)
+    ));
+    let res = write_index(root, &tmp, &index_file);
+    if res.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+    res
+}
+
+/// Write `index_file` to `tmp` and move it into place as the index of `root`
+fn write_index(root: &Path, tmp: &Path, index_file: &IndexFile) -> BinResult<()> {
+    let mut writer = BufWriter::new(std::fs::File::create(tmp)?);
+    index_file.write(&mut writer)?;
+    writer.into_inner().map_err(|e| e.into_error())?;
+    std::fs::rename(tmp, root.join(INDEX_FILE_NAME))?;
+
+    Ok(())
+}
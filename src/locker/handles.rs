@@ -0,0 +1,110 @@
+//! A bounded cache of open file handles
+//!
+//! Lockers may contain thousands of files, so rather than holding every
+//! file open, handles are opened when a record is first read from a file
+//! and the least recently used handle is closed when the cache is full.
+//!
+//! Handles are shared between threads, so records are read with positional
+//! reads that do not move a shared file cursor.
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+pub(super) struct FileCache {
+    capacity: usize,
+    /// The open handles, from least to most recently used
+    handles: VecDeque<(PathBuf, Arc<File>)>,
+}
+
+impl FileCache {
+    /// Create an empty cache that holds at most `capacity` handles
+    pub(super) fn new(capacity: usize) -> Self {
+        FileCache {
+            capacity: capacity.max(1),
+            handles: VecDeque::new(),
+        }
+    }
+
+    /// Return a handle to the file at `path`, opening it if necessary
+    ///
+    /// An evicted handle stays open until every reader using it is dropped.
+    pub(super) fn get(&mut self, path: &Path) -> io::Result<Arc<File>> {
+        match self.handles.iter().position(|(p, _)| p == path) {
+            Some(i) => {
+                // Move the handle to the most recently used position
+                let handle = self.handles.remove(i).expect("Handle index out of range");
+                self.handles.push_back(handle);
+            }
+            None => {
+                let f = Arc::new(File::open(path)?);
+                if self.handles.len() >= self.capacity {
+                    self.handles.pop_front();
+                }
+                self.handles.push_back((path.to_path_buf(), f));
+            }
+        }
+
+        let (_, f) = self.handles.back().expect("Cache is not empty");
+        Ok(Arc::clone(f))
+    }
+
+    /// Close every handle
+    pub(super) fn clear(&mut self) {
+        self.handles.clear();
+    }
+}
+
+/// A reader over a shared file handle with its own position
+///
+/// Reads use `pread` on Unix and `ReadFile` with an offset on Windows,
+/// so any number of readers can use the same handle at once.
+pub(super) struct PositionalReader {
+    file: Arc<File>,
+    pos: u64,
+}
+
+impl PositionalReader {
+    /// Create a reader positioned at the start of the file
+    pub(super) fn new(file: Arc<File>) -> Self {
+        PositionalReader { file, pos: 0 }
+    }
+}
+
+impl io::Read for PositionalReader {
+    #[cfg(unix)]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        use std::os::unix::fs::FileExt;
+        let n = self.file.read_at(buf, self.pos)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+
+    #[cfg(windows)]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        use std::os::windows::fs::FileExt;
+        let n = self.file.seek_read(buf, self.pos)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl io::Seek for PositionalReader {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            io::SeekFrom::Start(n) => {
+                self.pos = n;
+                return Ok(n);
+            }
+            io::SeekFrom::Current(n) => (self.pos, n),
+            io::SeekFrom::End(n) => (self.file.metadata()?.len(), n),
+        };
+
+        self.pos = base.checked_add_signed(offset).ok_or(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Invalid seek to a negative or overflowing position",
+        ))?;
+        Ok(self.pos)
+    }
+}
@@ -0,0 +1,1020 @@
+//! Lockers for sonar data
+use crate::model::{Channel, NavigationOffsets, RecordKind, SonarDataRecord};
+use crate::parser::{imagenex81b, jsf, xtf, Format};
+use binrw::io::BufReader;
+use binrw::BinRead;
+use glob::Pattern;
+use std::collections::{btree_map, BTreeMap};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use time::OffsetDateTime;
+
+use std::sync::{mpsc, Mutex, OnceLock};
+use std::thread;
+
+mod cache;
+mod handles;
+mod query;
+mod report;
+mod watch;
+
+pub use query::Query;
+pub use report::{FileReport, IndexReport, SkipReason, SkippedFile};
+pub use watch::Watch;
+
+use handles::{FileCache, PositionalReader};
+
+type LockerKey = (RecordKind, OffsetDateTime, Channel, u8, u32);
+type LockerValue = (PathBuf, u64, u16);
+
+/// A set of records that share a kind, timestamp, channel and subsystem
+///
+/// These are found when the locker index is built. Each of the records
+/// is kept in the index and they are distinguished by the sequence
+/// number in their keys.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Collision {
+    /// The kind of the records
+    pub kind: RecordKind,
+    /// The shared timestamp of the records
+    pub timestamp: OffsetDateTime,
+    /// The shared channel of the records
+    pub channel: Channel,
+    /// The shared subsystem of the records
+    pub subsystem: u8,
+    /// The file path, byte offset and position within the message of each
+    /// record, in sequence order
+    pub sources: Vec<LockerValue>,
+}
+
+/// The result of scanning the messages of a file
+struct Scan {
+    /// The key, byte offset and position within the message of each record
+    entries: Vec<(LockerKey, u64, u16)>,
+    /// The byte offset after the last message that was read
+    end: u64,
+    /// The offset of the message that could not be read and the error
+    error: Option<(u64, binrw::Error)>,
+}
+
+/// The metadata used to decide whether a file must be re-indexed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileInfo {
+    format: Format,
+    size: u64,
+    modified: SystemTime,
+}
+
+impl FileInfo {
+    /// Detect the format of the file at `path` and read its metadata
+    ///
+    /// Returns `Ok(None)` if the format is not recognized.
+    fn detect(path: &Path) -> std::io::Result<Option<Self>> {
+        let mut f = std::fs::File::open(path)?;
+        let format = match Format::detect(&mut f)? {
+            Some(format) => format,
+            None => return Ok(None),
+        };
+        let metadata = f.metadata()?;
+        Ok(Some(FileInfo {
+            format,
+            size: metadata.len(),
+            modified: metadata.modified()?,
+        }))
+    }
+}
+
+/// A file in the index
+#[derive(Debug)]
+struct IndexedFile {
+    info: FileInfo,
+    /// The byte offset after the last complete message that was indexed
+    end: u64,
+    /// The file header, read when the first record is read from the file
+    header: OnceLock<Option<xtf::FileHeader>>,
+}
+
+impl IndexedFile {
+    fn new(info: FileInfo, end: u64) -> Self {
+        IndexedFile {
+            info,
+            end,
+            header: OnceLock::new(),
+        }
+    }
+}
+
+/// A representation of an on-disk sonar data set
+///
+/// A locker may contain any mix of the file formats in [`Format`],
+/// in the locker directory or any of its subdirectories.
+/// The format of each file is detected from its leading bytes, and files
+/// that are not recognized are skipped and listed in the [`IndexReport`].
+///
+/// A `Locker` contains an in-memory [`BTreeMap`] index that maps
+/// keys to a file path and byte offset within that file where the desired
+/// record can be found, along with the position of the record among the
+/// records converted from the message at that offset.
+/// Keys are a tuple consisting of the [`RecordKind`] of the [`SonarDataRecord`],
+/// an [`OffsetDateTime`] representing the acquisition time
+/// of the measurement, a [`Channel`], a subsystem and a sequence number. Due to this key organization,
+/// queries such as finding all `SonarDataRecord::Ping` records from the
+/// `Channel::Port` between two times are fast. The channel is after the time
+/// because it is assumed that typical applications (i.e. mosaicking) will want to process
+/// starboard and port pings simultaneously.
+///
+/// The channel and subsystem keys only have meaning for the sonar data
+/// (`SonarDataRecord::Ping`). All other records default to `Channel::Other`
+/// and subsystem zero. The subsystem separates the pings of multi-frequency
+/// systems, which share channels (see [`Ping::subsystem`](crate::model::Ping::subsystem)).
+///
+/// The sequence number distinguishes records that would otherwise have
+/// the same key, such as records from files that overlap in time.
+/// It is zero for most records.
+/// Records sharing a key are numbered in order of their file path and offset,
+/// and are reported by [`Locker::collisions`].
+///
+/// The index is saved to a `.sdw-index` file inside the locker directory
+/// and reloaded the next time the locker is opened.
+///
+/// A `Locker` is [`Sync`], so records can be read from several threads at
+/// once. Each read uses its own file position, so threads never race on
+/// a shared file cursor.
+pub struct Locker {
+    path: PathBuf,
+    index: BTreeMap<LockerKey, LockerValue>,
+    files: BTreeMap<PathBuf, IndexedFile>,
+    filemap: Mutex<FileCache>,
+    collisions: Vec<Collision>,
+    report: IndexReport,
+    options: OpenOptions,
+}
+
+/// Options for opening a [`Locker`]
+///
+/// This follows the builder pattern of [`std::fs::OpenOptions`].
+/// [`Locker::open`] uses the default options.
+///
+/// ```
+/// # use sdw::locker::OpenOptions;
+/// # fn main() -> Result<(),Box<dyn std::error::Error>> {
+/// let locker = OpenOptions::new()
+///     .strict(true)
+///     .include("*.jsf")
+///     .exclude("**/raw/*")
+///     .open("assets/HE501")?;
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone)]
+pub struct OpenOptions {
+    strict: bool,
+    recursive: bool,
+    include: Vec<String>,
+    exclude: Vec<String>,
+    threads: usize,
+    open_files: usize,
+    companion_records: bool,
+    navigation_offsets: Option<NavigationOffsets>,
+}
+
+impl Default for OpenOptions {
+    fn default() -> Self {
+        OpenOptions {
+            strict: false,
+            recursive: true,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            open_files: 64,
+            companion_records: false,
+            navigation_offsets: None,
+        }
+    }
+}
+
+impl OpenOptions {
+    /// Create a new set of options with the default settings
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set whether subdirectories of the locker are indexed
+    ///
+    /// This is true by default, so that a locker organized as
+    /// `day/line/*.jsf` can be opened from its top-level directory.
+    /// Symbolic links to directories are not followed.
+    pub fn recursive(&mut self, recursive: bool) -> &mut Self {
+        self.recursive = recursive;
+        self
+    }
+
+    /// Only index files that match a glob pattern
+    ///
+    /// Patterns are matched against paths relative to the locker directory,
+    /// and `*` also matches path separators, so `*.jsf` matches JSF files at
+    /// any depth. This may be called more than once, in which case a file
+    /// must match any one of the patterns. By default every file is included.
+    pub fn include(&mut self, pattern: &str) -> &mut Self {
+        self.include.push(pattern.to_string());
+        self
+    }
+
+    /// Skip files and directories that match a glob pattern
+    ///
+    /// Patterns are matched as for [`OpenOptions::include`]. A directory that
+    /// matches is not traversed. Exclude patterns take precedence over
+    /// include patterns.
+    pub fn exclude(&mut self, pattern: &str) -> &mut Self {
+        self.exclude.push(pattern.to_string());
+        self
+    }
+
+    /// Set the number of worker threads used to scan files
+    ///
+    /// By default this is the available parallelism of the machine.
+    /// Each worker holds at most one file open while scanning.
+    pub fn threads(&mut self, threads: usize) -> &mut Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Set the maximum number of files held open for reading records
+    ///
+    /// Files are opened when a record is first read from them, and the
+    /// least recently used file is closed when the limit is reached.
+    /// The default is 64.
+    pub fn open_files(&mut self, open_files: usize) -> &mut Self {
+        self.open_files = open_files.max(1);
+        self
+    }
+
+    /// Set whether navigation records embedded in sonar data are indexed
+    ///
+    /// Some formats embed the position, attitude and course of the sonar
+    /// in each ping (see [`jsf::Message::companion_records`]). When this
+    /// is true, these are indexed as `Position`, `Orientation` and `Course`
+    /// records with the timestamp of the ping, in addition to the ping.
+    /// This is false by default.
+    pub fn companion_records(&mut self, companion_records: bool) -> &mut Self {
+        self.companion_records = companion_records;
+        self
+    }
+
+    /// Attach the offsets of the sonar from the navigation reference point
+    ///
+    /// The offsets are not applied to the records of the locker. They are
+    /// kept with the locker, and returned by [`Locker::navigation_offsets`],
+    /// for use when georeferencing. JSF files may record the offsets in a
+    /// navigation offsets message (see [`jsf::Message::navigation_offsets`]).
+    pub fn navigation_offsets(&mut self, offsets: NavigationOffsets) -> &mut Self {
+        self.navigation_offsets = Some(offsets);
+        self
+    }
+
+    /// Set whether any error while indexing a file fails the open
+    ///
+    /// By default, a file that cannot be read to the end is indexed up to
+    /// the first error and the error is recorded in the
+    /// [`IndexReport`]. In strict mode, [`OpenOptions::open`] instead
+    /// returns an error.
+    pub fn strict(&mut self, strict: bool) -> &mut Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Open a locker at the given path with these options
+    ///
+    /// See [`Locker::open`].
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error when `read_dir` errors, when an
+    /// include or exclude pattern is invalid, or in strict mode when any
+    /// file could not be indexed.
+    pub fn open<P>(&self, path: P) -> binrw::BinResult<Locker>
+    where
+        PathBuf: From<P>,
+    {
+        let index = BTreeMap::new();
+        let files = BTreeMap::new();
+        let filemap = Mutex::new(FileCache::new(self.open_files));
+        let collisions = Vec::new();
+        let report = IndexReport::default();
+        let path = PathBuf::from(path);
+
+        let mut locker = Locker {
+            path,
+            index,
+            files,
+            filemap,
+            collisions,
+            report,
+            options: self.clone(),
+        };
+
+        locker.build_index()?;
+        locker.check_strict()?;
+
+        Ok(locker)
+    }
+}
+
+impl Locker {
+    /// Open a locker at the given path
+    ///
+    /// ```
+    /// # use sdw::locker::Locker;
+    /// # fn main() -> Result<(),Box<dyn std::error::Error>> {
+    /// let locker = Locker::open("assets/HE501")?;
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// This scans through every file and creates an entry in
+    /// the locker tree for each record. This can take a while.
+    /// Each file is parsed according to the format detected from
+    /// its magic bytes, regardless of its extension.
+    ///
+    /// If the locker has been opened before, the saved index is reloaded
+    /// and only files that are new or whose size or modification time
+    /// have changed are scanned. The saved index is then updated. Failing
+    /// to save the index (e.g. on read-only media) is not an error.
+    ///
+    /// Errors in individual files do not cause this function to fail. They are
+    /// recorded in the [`IndexReport`] returned by [`Locker::report`]. Use
+    /// [`OpenOptions::strict`] to fail instead.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error when `read_dir` errors
+    pub fn open<P>(path: P) -> binrw::BinResult<Self>
+    where
+        PathBuf: From<P>,
+    {
+        OpenOptions::new().open(path)
+    }
+
+    fn build_index(&mut self) -> binrw::BinResult<()> {
+        // Clear the tree
+        self.index.clear();
+        self.files.clear();
+        self.filemap.get_mut().map_err(poisoned)?.clear();
+        let mut entries = Vec::new();
+        let mut reports = Vec::new();
+
+        // A missing, stale or corrupt saved index just means that
+        // every file is scanned
+        let mut cached =
+            cache::load(&self.path, self.options.companion_records).unwrap_or_default();
+        let mut changed = false;
+
+        let mut skipped = Vec::new();
+        let paths = self.walk(&mut skipped)?;
+
+        let mut jobs = Vec::new();
+
+        for filepath in paths {
+            // Detect the format of the file
+            let info = match FileInfo::detect(&filepath) {
+                Ok(Some(info)) => info,
+                Err(e) => {
+                    // The file is reported and the rest of the locker indexed
+                    reports.push(FileReport {
+                        path: filepath,
+                        format: None,
+                        records: 0,
+                        bytes_scanned: 0,
+                        cached: false,
+                        error: Some((0, e.into())),
+                    });
+                    continue;
+                }
+                Ok(None) => {
+                    skipped.push(SkippedFile {
+                        path: filepath,
+                        reason: SkipReason::UnknownFormat,
+                    });
+                    continue;
+                }
+            };
+
+            // Reuse the saved entries if the file is unchanged
+            if let Some((cached_info, cached_entries)) = cached.remove(&filepath) {
+                if cached_info == info {
+                    // Files with errors are never saved, so the whole file was indexed
+                    let end = info.size;
+                    self.files
+                        .insert(filepath.clone(), IndexedFile::new(info, end));
+                    reports.push(FileReport {
+                        path: filepath.clone(),
+                        format: Some(info.format),
+                        records: cached_entries.len(),
+                        bytes_scanned: 0,
+                        cached: true,
+                        error: None,
+                    });
+                    for (key, offset, position) in cached_entries {
+                        entries.push((key, (filepath.clone(), offset, position)));
+                    }
+                    continue;
+                }
+            }
+            changed = true;
+
+            jobs.push((filepath, info, 0));
+        }
+
+        for (filepath, info, scan) in scan_files(jobs, &self.options) {
+            let end = scan.end;
+            self.files
+                .insert(filepath.clone(), IndexedFile::new(info, end));
+            reports.push(FileReport {
+                path: filepath.clone(),
+                format: Some(info.format),
+                records: scan.entries.len(),
+                bytes_scanned: scan.end,
+                cached: false,
+                error: scan.error,
+            });
+            for (key, offset, position) in scan.entries {
+                entries.push((key, (filepath.clone(), offset, position)));
+            }
+        }
+
+        self.insert_entries(entries);
+        reports.sort_by(|a, b| a.path.cmp(&b.path));
+        skipped.sort_by(|a, b| a.path.cmp(&b.path));
+        self.report = IndexReport {
+            files: reports,
+            skipped,
+        };
+
+        // Anything left in the cache has been removed from the locker
+        if changed || !cached.is_empty() {
+            // Files with errors are left out of the saved index so that
+            // they are scanned, and their errors reported, every time
+            let files = self
+                .files
+                .iter()
+                .filter(|(path, _)| {
+                    self.report
+                        .errors()
+                        .all(|file| file.path.as_path() != path.as_path())
+                })
+                .map(|(path, file)| (path.clone(), file.info))
+                .collect();
+
+            // The saved index is only a cache, so failing to write it
+            // should not prevent the locker from being used
+            let _ = cache::save(
+                &self.path,
+                self.options.companion_records,
+                &files,
+                &self.index,
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Fail with the first indexing error if the locker is strict
+    fn check_strict(&self) -> binrw::BinResult<()> {
+        if self.options.strict {
+            if let Some(file) = self.report.errors().next() {
+                if let Some((offset, e)) = &file.error {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::Other,
+                        format!(
+                            "Error indexing {} at offset {}: {}",
+                            file.path.display(),
+                            offset,
+                            e
+                        ),
+                    )
+                    .into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Find the files to index in the locker directory
+    ///
+    /// Paths that are not indexed are added to `skipped`, except for the
+    /// saved index itself.
+    fn walk(&self, skipped: &mut Vec<SkippedFile>) -> binrw::BinResult<Vec<PathBuf>> {
+        let include = compile_patterns(&self.options.include)?;
+        let exclude = compile_patterns(&self.options.exclude)?;
+
+        let mut files = Vec::new();
+        let mut dirs = vec![self.path.clone()];
+        while let Some(dir) = dirs.pop() {
+            for entry in dir.read_dir()? {
+                let entry = entry?;
+                let path = entry.path();
+
+                if dir == self.path && cache::is_index_file(&path) {
+                    continue;
+                }
+
+                let relative = path.strip_prefix(&self.path).unwrap_or(&path);
+                let matches =
+                    |patterns: &[Pattern]| patterns.iter().any(|p| p.matches_path(relative));
+
+                let reason = if matches(&exclude) {
+                    SkipReason::Excluded
+                } else if entry.file_type()?.is_dir() {
+                    if self.options.recursive {
+                        dirs.push(path);
+                        continue;
+                    }
+                    SkipReason::Directory
+                } else if !path.is_file() {
+                    SkipReason::NotAFile
+                } else if !include.is_empty() && !matches(&include) {
+                    SkipReason::NotIncluded
+                } else {
+                    files.push(path);
+                    continue;
+                };
+
+                skipped.push(SkippedFile { path, reason });
+            }
+        }
+
+        files.sort();
+        Ok(files)
+    }
+
+    /// Insert entries into the tree, numbering entries with the same key
+    ///
+    /// Entries are sorted by key and then by file path and offset before
+    /// they are numbered, so that the sequence numbers do not depend
+    /// on the order in which the files were scanned.
+    fn insert_entries(&mut self, mut entries: Vec<(LockerKey, LockerValue)>) {
+        self.collisions.clear();
+        entries.sort_unstable();
+
+        let mut previous: Option<LockerKey> = None;
+        for ((kind, timestamp, channel, subsystem, _), value) in entries {
+            let sequence = match previous {
+                Some((k, t, c, ss, s))
+                    if (k, t, c, ss) == (kind, timestamp, channel, subsystem) =>
+                {
+                    s + 1
+                }
+                _ => 0,
+            };
+
+            if sequence == 1 {
+                let first = self.index[&(kind, timestamp, channel, subsystem, 0)].clone();
+                self.collisions.push(Collision {
+                    kind,
+                    timestamp,
+                    channel,
+                    subsystem,
+                    sources: vec![first],
+                });
+            }
+            if sequence > 0 {
+                if let Some(collision) = self.collisions.last_mut() {
+                    collision.sources.push(value.clone());
+                }
+            }
+
+            let key = (kind, timestamp, channel, subsystem, sequence);
+            self.index.insert(key, value);
+            previous = Some(key);
+        }
+    }
+
+    /// Return the records that share a kind, timestamp, channel and subsystem
+    ///
+    /// Every record is kept in the index, but large numbers of collisions
+    /// may indicate duplicated files in the locker.
+    pub fn collisions(&self) -> &[Collision] {
+        &self.collisions
+    }
+
+    /// Return the report on the indexing of the locker
+    ///
+    /// ```
+    /// # use sdw::locker::Locker;
+    /// # fn main() -> Result<(),Box<dyn std::error::Error>> {
+    /// let locker = Locker::open("assets/HE501")?;
+    /// for file in locker.report().errors() {
+    ///     if let Some((offset, e)) = &file.error {
+    ///         println!("{}: {} at offset {}", file.path.display(), e, offset);
+    ///     }
+    /// }
+    /// # Ok(()) }
+    /// ```
+    pub fn report(&self) -> &IndexReport {
+        &self.report
+    }
+
+    /// Return a reference to the path of the locker
+    ///
+    /// ```
+    /// # use sdw::locker::Locker;
+    /// # use std::path::Path;
+    /// # fn main() -> Result<(),Box<dyn std::error::Error>> {
+    /// let locker = Locker::open("assets/HE501")?;
+    /// assert_eq!(locker.path(),Path::new("assets/HE501"));
+    /// # Ok(()) }
+    /// ```
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Return the offsets of the sonar from the navigation reference point
+    ///
+    /// These are `None` unless they were attached with
+    /// [`OpenOptions::navigation_offsets`] or
+    /// [`Locker::set_navigation_offsets`].
+    pub fn navigation_offsets(&self) -> Option<&NavigationOffsets> {
+        self.options.navigation_offsets.as_ref()
+    }
+
+    /// Attach the offsets of the sonar from the navigation reference point
+    ///
+    /// See [`OpenOptions::navigation_offsets`].
+    pub fn set_navigation_offsets(&mut self, offsets: Option<NavigationOffsets>) {
+        self.options.navigation_offsets = offsets;
+    }
+
+    /// Return a reference to the underlying [`BTreeMap`] index
+    pub fn index(&self) -> &BTreeMap<LockerKey, LockerValue> {
+        &self.index
+    }
+
+    /// Get an iterator over the entries of the locker, sorted by key
+    pub fn iter(&self) -> Iter {
+        let iter = self.index.iter();
+        Iter { iter }
+    }
+
+    /// Start a query over the records in the locker
+    ///
+    /// See [`Query`] for the available constraints.
+    pub fn query(&self) -> Query {
+        Query::new(self)
+    }
+
+    fn read_record(
+        &self,
+        path: &PathBuf,
+        offset: &u64,
+        position: &u16,
+        key: &LockerKey,
+    ) -> binrw::BinResult<SonarDataRecord<f32>> {
+        let file = self.files.get(path).ok_or(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            "Key not found",
+        ))?;
+        let format = file.info.format;
+
+        // Find or open the file handle in the filemap. The lock is only
+        // held while looking up the handle, so reads can run concurrently.
+        let handle = self.filemap.lock().map_err(poisoned)?.get(path)?;
+        let mut f = BufReader::new(PositionalReader::new(handle));
+
+        // Messages in some formats can only be read with the file header,
+        // which is read once per file
+        let header = match file.header.get() {
+            Some(header) => header,
+            None => {
+                let _ = file.header.set(read_header(format, &mut f)?);
+                file.header.get().unwrap_or(&None)
+            }
+        };
+
+        // Seek to the desired offset
+        f.seek(SeekFrom::Start(*offset))?;
+
+        // Read a message in the file's format and take the record at the
+        // indexed position, which may be one of several pings or a
+        // companion record. The key is checked in case the file changed.
+        read_message(format, header.as_ref(), &mut f, true)?
+            .into_iter()
+            .nth(usize::from(*position))
+            .filter(|rec| match record_key(rec) {
+                Some((kind, timestamp, channel, subsystem, _)) => {
+                    (kind, timestamp, channel, subsystem) == (key.0, key.1, key.2, key.3)
+                }
+                None => false,
+            })
+            .ok_or_else(|| {
+                std::io::Error::new(std::io::ErrorKind::Other, "Record not found").into()
+            })
+    }
+
+    /// Get the SonarDataRecord identified by the key
+    ///
+    /// ```
+    /// # use sdw::locker::{create_key,Locker};
+    /// # fn get_test() -> Result<(), Box<dyn std::error::Error>> {
+    /// let locker = Locker::open("assets/HE501")?;
+    /// let (k, _) = locker
+    ///              .index()
+    ///              .first_key_value()
+    ///              .ok_or(std::io::Error::new(std::io::ErrorKind::Other,
+    ///                                         "Key not found"))?;
+    /// let rec = locker.get(k)?;    
+    /// let c = create_key(rec).ok_or(std::io::Error::new(
+    ///         std::io::ErrorKind::Other,
+    ///         "Unknown record retrieved",
+    ///         ))?;
+
+    /// assert_eq!(c.0, k.0);
+    /// assert_eq!(c.1, k.1);
+    /// assert_eq!(c.2, k.2);
+    /// # Ok(()) }
+    /// ```
+    /// # Errors
+    ///
+    /// This method returns an error if the key is not found in the index tree or
+    /// if there is an error reading the record from the file.
+    pub fn get(&self, key: &LockerKey) -> binrw::BinResult<SonarDataRecord<f32>> {
+        let (path, offset, position) = self.index.get(key).ok_or(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            "Key not found",
+        ))?;
+        self.read_record(path, offset, position, key)
+    }
+}
+
+fn poisoned<T>(_: T) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Other, "File handle cache poisoned")
+}
+
+/// Scan files on a pool of at most `threads` worker threads
+///
+/// Each job is a file and the byte offset to start scanning from.
+fn scan_files(
+    jobs: Vec<(PathBuf, FileInfo, u64)>,
+    options: &OpenOptions,
+) -> Vec<(PathBuf, FileInfo, Scan)> {
+    let workers = options.threads.min(jobs.len());
+    let jobs = Mutex::new(jobs.into_iter());
+    let (tx, rx) = mpsc::channel();
+
+    thread::scope(|s| {
+        for _ in 0..workers {
+            let tx1 = tx.clone();
+            let jobs = &jobs;
+            s.spawn(move || loop {
+                // A worker only panics while scanning, after releasing the lock
+                let job = jobs.lock().map(|mut jobs| jobs.next());
+                let (filepath, info, start) = match job {
+                    Ok(Some(job)) => job,
+                    _ => break,
+                };
+                let scan = scan_file(
+                    &filepath,
+                    info.format,
+                    start,
+                    info.size,
+                    options.companion_records,
+                );
+                // The receiver outlives every sender
+                let _ = tx1.send((filepath, info, scan));
+            });
+        }
+    });
+
+    // Explicitly drop the Sender to close the channel
+    drop(tx);
+    rx.into_iter().collect()
+}
+
+fn compile_patterns(patterns: &[String]) -> std::io::Result<Vec<Pattern>> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            Pattern::new(pattern).map_err(|e| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("Invalid pattern {}: {}", pattern, e),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Scan the messages in a file of `size` bytes from the offset `start`
+///
+/// A `start` of zero scans the whole file, including any file header.
+/// Otherwise `start` must be the offset of a message. The scan stops at the
+/// first message that cannot be read, including a message that is cut off
+/// by the end of the file.
+fn scan_file(path: &Path, format: Format, start: u64, size: u64, companions: bool) -> Scan {
+    let mut scan = Scan {
+        entries: Vec::new(),
+        end: start,
+        error: None,
+    };
+
+    if let Err(e) = scan_messages(path, format, size, companions, &mut scan) {
+        scan.error = Some((scan.end, e));
+    }
+
+    scan
+}
+
+/// Read the messages of a file that start before `size`
+///
+/// Data appended after the size of the file was taken are left for the
+/// next refresh, so a file that grows while it is scanned is not an
+/// error.
+fn scan_messages(
+    path: &Path,
+    format: Format,
+    size: u64,
+    companions: bool,
+    scan: &mut Scan,
+) -> binrw::BinResult<()> {
+    let mut reader = BufReader::new(std::fs::File::open(path)?);
+    let header = read_header(format, &mut reader)?;
+    if scan.end == 0 {
+        scan.end = reader.stream_position()?;
+    } else {
+        reader.seek(SeekFrom::Start(scan.end))?;
+    }
+
+    while scan.end < size {
+        let records = read_message(format, header.as_ref(), &mut reader, companions)?;
+        for (position, rec) in records.into_iter().enumerate() {
+            if let Some(key) = create_key(rec) {
+                scan.entries.push((key, scan.end, position as u16));
+            }
+        }
+        scan.end = reader.stream_position()?;
+    }
+    Ok(())
+}
+
+/// Read the file-level header that precedes the first message, if any
+///
+/// The reader is left at the first message.
+fn read_header<R: Read + Seek>(
+    format: Format,
+    reader: &mut R,
+) -> binrw::BinResult<Option<xtf::FileHeader>> {
+    match format {
+        Format::Xtf => {
+            reader.seek(SeekFrom::Start(0))?;
+            Ok(Some(xtf::FileHeader::read(reader)?))
+        }
+        Format::Jsf | Format::Imagenex81b => Ok(None),
+    }
+}
+
+/// Read a single message in the given format and convert it to SonarDataRecords
+///
+/// The record converted from the message comes first, followed by any
+/// companion records if `companions` is true.
+fn read_message<R: Read + Seek>(
+    format: Format,
+    header: Option<&xtf::FileHeader>,
+    reader: &mut R,
+    companions: bool,
+) -> binrw::BinResult<Vec<SonarDataRecord<f32>>> {
+    match format {
+        Format::Jsf => {
+            let msg = jsf::Message::read(reader)?;
+            let companions = if companions {
+                msg.companion_records()
+            } else {
+                Vec::new()
+            };
+            let mut records = vec![SonarDataRecord::from(msg)];
+            records.extend(companions);
+            Ok(records)
+        }
+        Format::Imagenex81b => Ok(vec![SonarDataRecord::from(imagenex81b::Shot::read(
+            reader,
+        )?)]),
+        Format::Xtf => {
+            let header = header.ok_or_else(|| {
+                std::io::Error::new(std::io::ErrorKind::Other, "Missing XTF file header")
+            })?;
+            let formats = header.channel_formats();
+            let packet = xtf::Packet::read_args(reader, (&formats,))?;
+            let mut records = packet.records(header);
+            if companions {
+                records.extend(packet.companion_records(header));
+            }
+            Ok(records)
+        }
+    }
+}
+
+/// Create a LockerKey from a SonarDataRecord
+///
+/// Returns `None` if the rec is `SonarDataRecord::Unknown`, otherwise returns
+/// `Some(key)` with an appropriately formatted key. The sequence number of
+/// the key is zero.
+pub fn create_key<T>(rec: SonarDataRecord<T>) -> Option<LockerKey> {
+    record_key(&rec)
+}
+
+fn record_key<T>(rec: &SonarDataRecord<T>) -> Option<LockerKey> {
+    match rec {
+        SonarDataRecord::Ping(data) => Some((
+            RecordKind::Ping,
+            data.timestamp,
+            data.channel,
+            data.subsystem,
+            0,
+        )),
+        SonarDataRecord::Course(data) => {
+            Some((RecordKind::Course, data.timestamp, Channel::default(), 0, 0))
+        }
+        SonarDataRecord::Position(data) => Some((
+            RecordKind::Position,
+            data.timestamp,
+            Channel::default(),
+            0,
+            0,
+        )),
+        SonarDataRecord::Orientation(data) => Some((
+            RecordKind::Orientation,
+            data.timestamp,
+            Channel::default(),
+            0,
+            0,
+        )),
+        SonarDataRecord::Bathymetry(data) => {
+            Some((RecordKind::Bathymetry, data.timestamp, data.channel, 0, 0))
+        }
+        SonarDataRecord::Unknown => None,
+    }
+}
+
+/// An iterator over the entries of the locker
+///
+/// This should be created by calling `iter` on a `Locker`.
+///
+/// ```
+/// # use sdw::locker::Locker;
+/// # use std::path::Path;
+/// # fn main() -> Result<(),Box<dyn std::error::Error>> {
+/// let locker = Locker::open("assets/HE501")?;
+/// let c = locker.iter().count();
+/// assert_eq!(436719,c);
+/// # Ok(()) }
+/// ```
+pub struct Iter<'a> {
+    iter: btree_map::Iter<'a, LockerKey, LockerValue>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a LockerKey, &'a LockerValue);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a> ExactSizeIterator for Iter<'a> {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ping(time: i32) -> Vec<u8> {
+        let mut msg = vec![0x01, 0x16, 0, 0, 80, 0, 0, 20, 0, 0, 0, 0];
+        msg.extend_from_slice(&240i32.to_le_bytes());
+        msg.extend_from_slice(&time.to_le_bytes());
+        msg.extend_from_slice(&[0; 236]);
+        msg
+    }
+
+    #[test]
+    fn test_scan_growing_file() {
+        let path = std::env::temp_dir().join(format!("sdw-scan-{}.jsf", std::process::id()));
+        std::fs::write(&path, [ping(1_600_000_000), ping(1_600_000_001)].concat()).unwrap();
+
+        // Data written after the size was taken is left for later
+        let scan = scan_file(&path, Format::Jsf, 0, 256, false);
+        assert!(scan.error.is_none());
+        assert_eq!(256, scan.end);
+        assert_eq!(1, scan.entries.len());
+
+        let scan = scan_file(&path, Format::Jsf, 256, 512, false);
+        assert!(scan.error.is_none());
+        assert_eq!(512, scan.end);
+        assert_eq!(1, scan.entries.len());
+
+        // A file that is shorter than its size is cut off
+        let scan = scan_file(&path, Format::Jsf, 0, 600, false);
+        assert_eq!(512, scan.error.unwrap().0);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,197 @@
+//! Typed queries over the records in a locker
+use super::{Locker, LockerKey, LockerValue};
+use crate::model::{Channel, RecordKind, SonarDataRecord};
+use std::collections::HashSet;
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+use time::{OffsetDateTime, PrimitiveDateTime};
+
+/// A query over the records in a [`Locker`]
+///
+/// A `Query` is created with [`Locker::query`] and narrowed down by
+/// record kind, time window, channel, subsystem and source file. Every constraint
+/// is optional, and a query without constraints matches every record
+/// in the locker. Matching records are returned in key order.
+///
+/// ```
+/// # use sdw::locker::Locker;
+/// # use sdw::model::{Channel, RecordKind};
+/// # use time::macros::datetime;
+/// # fn main() -> Result<(),Box<dyn std::error::Error>> {
+/// let locker = Locker::open("assets/HE501")?;
+/// let pings = locker
+///     .query()
+///     .kind(RecordKind::Ping)
+///     .start(datetime!(2015-07-11 0:00 UTC))
+///     .end(datetime!(2015-07-12 0:00 UTC))
+///     .channel(Channel::Port)
+///     .records();
+/// for ping in pings {
+///     let ping = ping?;
+/// }
+/// # Ok(()) }
+/// ```
+pub struct Query<'a> {
+    locker: &'a Locker,
+    kind: Option<RecordKind>,
+    start: Option<OffsetDateTime>,
+    end: Option<OffsetDateTime>,
+    channels: Option<HashSet<Channel>>,
+    subsystems: Option<HashSet<u8>>,
+    files: Option<Vec<PathBuf>>,
+}
+
+impl<'a> Query<'a> {
+    pub(super) fn new(locker: &'a Locker) -> Self {
+        Query {
+            locker,
+            kind: None,
+            start: None,
+            end: None,
+            channels: None,
+            subsystems: None,
+            files: None,
+        }
+    }
+
+    /// Only match records of the given kind
+    pub fn kind(mut self, kind: RecordKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    /// Only match records acquired at or after `start`
+    pub fn start(mut self, start: OffsetDateTime) -> Self {
+        self.start = Some(start);
+        self
+    }
+
+    /// Only match records acquired before `end`
+    pub fn end(mut self, end: OffsetDateTime) -> Self {
+        self.end = Some(end);
+        self
+    }
+
+    /// Match records on the given channel
+    ///
+    /// This may be called more than once to match several channels.
+    pub fn channel(self, channel: Channel) -> Self {
+        self.channels([channel])
+    }
+
+    /// Match records on any of the given channels
+    pub fn channels<I>(mut self, channels: I) -> Self
+    where
+        I: IntoIterator<Item = Channel>,
+    {
+        self.channels
+            .get_or_insert_with(HashSet::new)
+            .extend(channels);
+        self
+    }
+
+    /// Match records from the given subsystem
+    ///
+    /// This separates the pings of multi-frequency systems. Records other
+    /// than pings are from subsystem zero. This may be called more than
+    /// once to match several subsystems.
+    pub fn subsystem(mut self, subsystem: u8) -> Self {
+        self.subsystems
+            .get_or_insert_with(HashSet::new)
+            .insert(subsystem);
+        self
+    }
+
+    /// Match records read from the given file
+    ///
+    /// The path matches any file in the locker whose path ends with it,
+    /// so it may be given relative to the locker directory. This may be
+    /// called more than once to match several files.
+    pub fn file<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.files
+            .get_or_insert_with(Vec::new)
+            .push(path.as_ref().to_path_buf());
+        self
+    }
+
+    fn matches(&self, key: &LockerKey, value: &LockerValue) -> bool {
+        let (_, timestamp, channel, subsystem, _) = key;
+        let (path, _, _) = value;
+
+        if let Some(start) = self.start {
+            if *timestamp < start {
+                return false;
+            }
+        }
+
+        if let Some(end) = self.end {
+            if *timestamp >= end {
+                return false;
+            }
+        }
+
+        if let Some(channels) = &self.channels {
+            if !channels.contains(channel) {
+                return false;
+            }
+        }
+
+        if let Some(subsystems) = &self.subsystems {
+            if !subsystems.contains(subsystem) {
+                return false;
+            }
+        }
+
+        if let Some(files) = &self.files {
+            if !files.iter().any(|file| path.ends_with(file)) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Return an iterator over the matching entries of the locker index
+    pub fn entries(self) -> impl Iterator<Item = (&'a LockerKey, &'a LockerValue)> {
+        let index = &self.locker.index;
+        // An empty time window would be an invalid range of the index
+        let empty = matches!((self.start, self.end), (Some(start), Some(end)) if start >= end);
+        let entries: Box<dyn Iterator<Item = (&'a LockerKey, &'a LockerValue)>> = match self.kind {
+            _ if empty => Box::new(std::iter::empty()),
+            Some(kind) => {
+                // Keys are ordered by kind and then by time, so the
+                // time window bounds a contiguous range of the index
+                let start = self
+                    .start
+                    .unwrap_or_else(|| PrimitiveDateTime::MIN.assume_utc());
+                let lower = Bound::Included((kind, start, Channel::Port, 0, 0));
+                let upper = match self.end {
+                    Some(end) => Bound::Excluded((kind, end, Channel::Port, 0, 0)),
+                    None => Bound::Included((
+                        kind,
+                        PrimitiveDateTime::MAX.assume_utc(),
+                        Channel::Other,
+                        u8::MAX,
+                        u32::MAX,
+                    )),
+                };
+                Box::new(index.range((lower, upper)))
+            }
+            None => Box::new(index.iter()),
+        };
+        entries.filter(move |(key, value)| self.matches(key, value))
+    }
+
+    /// Return an iterator over the matching keys
+    pub fn keys(self) -> impl Iterator<Item = &'a LockerKey> {
+        self.entries().map(|(key, _)| key)
+    }
+
+    /// Return an iterator that reads and decodes each matching record
+    pub fn records(self) -> impl Iterator<Item = binrw::BinResult<SonarDataRecord<f32>>> + 'a {
+        let locker = self.locker;
+        self.entries().map(move |(key, (path, offset, position))| {
+            locker.read_record(path, offset, position, key)
+        })
+    }
+}
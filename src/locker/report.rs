@@ -0,0 +1,82 @@
+//! Reports on the indexing of a locker
+use crate::parser::Format;
+use std::path::PathBuf;
+
+/// The outcome of indexing a single file in a locker
+#[derive(Debug)]
+pub struct FileReport {
+    /// The path of the file
+    pub path: PathBuf,
+    /// The format of the file
+    ///
+    /// This is `None` if the file could not be read to detect its format,
+    /// in which case the error is reported at offset zero.
+    pub format: Option<Format>,
+    /// The number of records from the file in the index
+    pub records: usize,
+    /// The number of bytes that were scanned
+    ///
+    /// This is zero if the entries were reloaded from the saved index.
+    pub bytes_scanned: u64,
+    /// Whether the entries were reloaded from the saved index
+    pub cached: bool,
+    /// The first error encountered while scanning the file
+    ///
+    /// This is the byte offset of the message that could not be read
+    /// and the error that occurred. Records before this offset are
+    /// still indexed, but the rest of the file is not.
+    pub error: Option<(u64, binrw::Error)>,
+}
+
+/// The reason that a path in a locker was not indexed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    /// The path matched an exclude pattern
+    Excluded,
+    /// Include patterns were given and the path matched none of them
+    NotIncluded,
+    /// The path is a directory and the locker is not recursive
+    Directory,
+    /// The path is neither a file nor a directory
+    NotAFile,
+    /// The format of the file was not recognized
+    UnknownFormat,
+}
+
+/// A path in a locker that was not indexed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedFile {
+    /// The path that was skipped
+    pub path: PathBuf,
+    /// The reason that the path was skipped
+    pub reason: SkipReason,
+}
+
+/// A report on the indexing of a locker
+///
+/// This is available from [`Locker::report`](super::Locker::report)
+/// after the locker is opened.
+#[derive(Debug, Default)]
+pub struct IndexReport {
+    /// The outcome for each file in the locker, sorted by path
+    pub files: Vec<FileReport>,
+    /// The paths that were not indexed, sorted by path
+    pub skipped: Vec<SkippedFile>,
+}
+
+impl IndexReport {
+    /// Return an iterator over the reports of files that had errors
+    pub fn errors(&self) -> impl Iterator<Item = &FileReport> {
+        self.files.iter().filter(|file| file.error.is_some())
+    }
+
+    /// Return true if every file was indexed without errors
+    pub fn is_ok(&self) -> bool {
+        self.errors().next().is_none()
+    }
+
+    /// Return the total number of records indexed
+    pub fn records(&self) -> usize {
+        self.files.iter().map(|file| file.records).sum()
+    }
+}
@@ -0,0 +1,246 @@
+//! Following lockers that are still being written
+//!
+//! During acquisition, new messages are appended to the files in a locker
+//! and new files are created. [`Locker::refresh`] brings the index up to
+//! date without rescanning the files that have only grown, and [`Watch`]
+//! polls a locker for new records.
+use super::{
+    poisoned, scan_files, FileInfo, FileReport, IndexedFile, Locker, LockerKey, LockerValue,
+    SkipReason, SkippedFile,
+};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::Duration;
+
+impl Locker {
+    /// Bring the index up to date with the files in the locker
+    ///
+    /// Files that have grown since they were indexed are scanned from the
+    /// end of the last message that was indexed, and new files are
+    /// scanned in full. Files that have shrunk, changed format, or were
+    /// modified without growing are re-indexed, and the records of files
+    /// that were removed are dropped. Files that cannot be read are
+    /// reported with an error and treated as removed until they can be
+    /// read again. A message that is cut off by the end of a file is assumed
+    /// to be still being written. It is not reported as an error, and is
+    /// indexed by a later refresh once it is complete.
+    ///
+    /// Returns the keys of the records that were added to the index.
+    /// Sequence numbers are reassigned, so the keys of existing records
+    /// may change if new records share their kind, timestamp and channel.
+    ///
+    /// The saved index is not updated. It is brought up to date the next
+    /// time that the locker is opened.
+    ///
+    /// ```
+    /// # use sdw::locker::Locker;
+    /// # fn main() -> Result<(),Box<dyn std::error::Error>> {
+    /// let mut locker = Locker::open("assets/HE501")?;
+    /// for key in locker.refresh()? {
+    ///     let record = locker.get(&key)?;
+    /// }
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error when `read_dir` errors. Errors
+    /// within files are recorded in the [`IndexReport`](super::IndexReport),
+    /// and cause an error in strict mode.
+    pub fn refresh(&mut self) -> binrw::BinResult<Vec<LockerKey>> {
+        let mut skipped = Vec::new();
+        let paths = self.walk(&mut skipped)?;
+
+        let mut jobs = Vec::new();
+        let mut stale: HashSet<PathBuf> = self.files.keys().cloned().collect();
+        let mut rewritten = HashSet::new();
+        let mut unreadable = Vec::new();
+
+        for filepath in paths {
+            let info = match FileInfo::detect(&filepath) {
+                Ok(Some(info)) => info,
+                Err(e) => {
+                    unreadable.push(FileReport {
+                        path: filepath,
+                        format: None,
+                        records: 0,
+                        bytes_scanned: 0,
+                        cached: false,
+                        error: Some((0, e.into())),
+                    });
+                    continue;
+                }
+                Ok(None) => {
+                    skipped.push(SkippedFile {
+                        path: filepath,
+                        reason: SkipReason::UnknownFormat,
+                    });
+                    continue;
+                }
+            };
+
+            let start = match self.files.get(&filepath) {
+                Some(file) if file.info == info => {
+                    stale.remove(&filepath);
+                    continue;
+                }
+                Some(file) if file.info.format == info.format && file.info.size < info.size => {
+                    stale.remove(&filepath);
+                    file.end
+                }
+                Some(_) => {
+                    rewritten.insert(filepath.clone());
+                    0
+                }
+                None => 0,
+            };
+            jobs.push((filepath, info, start));
+        }
+
+        // Files that could not be read are reported afresh by every refresh
+        self.report.files.retain(|file| file.format.is_some());
+        self.report.files.extend(unreadable);
+
+        if jobs.is_empty() && stale.is_empty() && rewritten.is_empty() {
+            self.report.files.sort_by(|a, b| a.path.cmp(&b.path));
+            skipped.sort_by(|a, b| a.path.cmp(&b.path));
+            self.report.skipped = skipped;
+            self.check_strict()?;
+            return Ok(Vec::new());
+        }
+
+        // Open handles may refer to files that have been replaced
+        if !stale.is_empty() || !rewritten.is_empty() {
+            self.filemap.get_mut().map_err(poisoned)?.clear();
+        }
+        for path in stale.iter().chain(rewritten.iter()) {
+            self.files.remove(path);
+        }
+        self.report.files.retain(|file| {
+            file.format.is_none() || !stale.contains(&file.path) && !rewritten.contains(&file.path)
+        });
+
+        let mut entries: Vec<(LockerKey, LockerValue)> = std::mem::take(&mut self.index)
+            .into_iter()
+            .filter(|(_, (path, _, _))| !stale.contains(path) && !rewritten.contains(path))
+            .collect();
+        let mut added: HashSet<LockerValue> = HashSet::new();
+        for (filepath, info, mut scan) in scan_files(jobs, &self.options) {
+            // The last message may still be being written
+            if matches!(&scan.error, Some((_, e)) if is_truncated(e)) {
+                scan.error = None;
+            }
+
+            let start = self.files.get(&filepath).map_or(0, |file| file.end);
+            let end = scan.end;
+            self.files
+                .insert(filepath.clone(), IndexedFile::new(info, end));
+            for (key, offset, position) in &scan.entries {
+                added.insert((filepath.clone(), *offset, *position));
+                entries.push((*key, (filepath.clone(), *offset, *position)));
+            }
+
+            let bytes_scanned = scan.end - start;
+            match self.report.files.iter_mut().find(|f| f.path == filepath) {
+                Some(report) => {
+                    report.records += scan.entries.len();
+                    report.bytes_scanned += bytes_scanned;
+                    report.error = scan.error;
+                }
+                None => self.report.files.push(FileReport {
+                    path: filepath,
+                    format: Some(info.format),
+                    records: scan.entries.len(),
+                    bytes_scanned,
+                    cached: false,
+                    error: scan.error,
+                }),
+            }
+        }
+
+        // Sequence numbers are assigned as if the locker had been opened afresh
+        self.insert_entries(entries);
+
+        self.report.files.sort_by(|a, b| a.path.cmp(&b.path));
+        skipped.sort_by(|a, b| a.path.cmp(&b.path));
+        self.report.skipped = skipped;
+        self.check_strict()?;
+
+        Ok(self
+            .index
+            .iter()
+            .filter(|(_, value)| added.contains(*value))
+            .map(|(key, _)| *key)
+            .collect())
+    }
+
+    /// Poll the locker for new records every `interval`
+    ///
+    /// The returned [`Watch`] is an iterator that calls [`Locker::refresh`]
+    /// until it finds new records or an error occurs, sleeping for
+    /// `interval` between calls. It never ends, so it should be driven
+    /// with `while let` so that the locker can be borrowed through
+    /// [`Watch::locker`] to read the new records.
+    ///
+    /// ```no_run
+    /// # use sdw::locker::Locker;
+    /// # use std::time::Duration;
+    /// # fn main() -> Result<(),Box<dyn std::error::Error>> {
+    /// let mut locker = Locker::open("assets/HE501")?;
+    /// let mut watch = locker.watch(Duration::from_secs(1));
+    /// while let Some(keys) = watch.next() {
+    ///     for key in keys? {
+    ///         let record = watch.locker().get(&key)?;
+    ///     }
+    /// }
+    /// # Ok(()) }
+    /// ```
+    pub fn watch(&mut self, interval: Duration) -> Watch<'_> {
+        Watch {
+            locker: self,
+            interval,
+        }
+    }
+}
+
+/// Return true if `e` was caused by reaching the end of the file
+///
+/// When a message is cut off, the variants of a message enum fail with a
+/// mix of end of file errors and assertion failures.
+fn is_truncated(e: &binrw::Error) -> bool {
+    match e {
+        binrw::Error::Backtrace(backtrace) => is_truncated(&backtrace.error),
+        binrw::Error::EnumErrors { variant_errors, .. } => {
+            variant_errors.iter().any(|(_, e)| is_truncated(e))
+        }
+        e => e.is_eof(),
+    }
+}
+
+/// A polling watcher over a locker
+///
+/// This is created by [`Locker::watch`].
+pub struct Watch<'a> {
+    locker: &'a mut Locker,
+    interval: Duration,
+}
+
+impl<'a> Watch<'a> {
+    /// Return the locker that is being watched
+    pub fn locker(&self) -> &Locker {
+        self.locker
+    }
+}
+
+impl<'a> Iterator for Watch<'a> {
+    type Item = binrw::BinResult<Vec<LockerKey>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.locker.refresh() {
+                Ok(keys) if keys.is_empty() => std::thread::sleep(self.interval),
+                res => return Some(res),
+            }
+        }
+    }
+}
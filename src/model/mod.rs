@@ -1,5 +1,7 @@
 //! The SDW data model
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
 use time::OffsetDateTime;
 
 /// The channel for multi-channel sensors
@@ -20,6 +22,79 @@ pub enum Channel {
     Other,
 }
 
+/// The kind of data held by a [`SonarDataRecord`]
+///
+/// There is one kind for each variant of [`SonarDataRecord`]. The
+/// string representation of a kind is the name of the variant.
+///
+/// ```
+/// # use sdw::model::RecordKind;
+/// assert_eq!(RecordKind::Ping.to_string(), "Ping");
+/// assert_eq!("Ping".parse(), Ok(RecordKind::Ping));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum RecordKind {
+    /// A [`Ping`]
+    Ping,
+    /// A [`Position`]
+    Position,
+    /// An [`Orientation`]
+    Orientation,
+    /// A [`Course`]
+    Course,
+    /// A [`Bathymetry`]
+    Bathymetry,
+    /// An unknown record
+    Unknown,
+}
+
+impl RecordKind {
+    fn name(&self) -> &'static str {
+        match self {
+            RecordKind::Ping => "Ping",
+            RecordKind::Position => "Position",
+            RecordKind::Orientation => "Orientation",
+            RecordKind::Course => "Course",
+            RecordKind::Bathymetry => "Bathymetry",
+            RecordKind::Unknown => "Unknown",
+        }
+    }
+}
+
+impl fmt::Display for RecordKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.name())
+    }
+}
+
+/// The error returned when a string is not the name of a [`RecordKind`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseRecordKindError(String);
+
+impl fmt::Display for ParseRecordKindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unknown record kind: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseRecordKindError {}
+
+impl FromStr for RecordKind {
+    type Err = ParseRecordKindError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Ping" => Ok(RecordKind::Ping),
+            "Position" => Ok(RecordKind::Position),
+            "Orientation" => Ok(RecordKind::Orientation),
+            "Course" => Ok(RecordKind::Course),
+            "Bathymetry" => Ok(RecordKind::Bathymetry),
+            "Unknown" => Ok(RecordKind::Unknown),
+            _ => Err(ParseRecordKindError(s.to_string())),
+        }
+    }
+}
+
 /// A representation of a sonar ping
 ///
 #[derive(Debug, Deserialize, Serialize)]
@@ -37,6 +112,13 @@ pub struct Ping<T> {
     pub sampling_interval: f64,
     /// The channel (Port, Starboard, Other)
     pub channel: Channel,
+    /// The subsystem of the sonar that acquired the ping
+    ///
+    /// Multi-frequency sonars acquire pings on several subsystems, which
+    /// share channels. For example, Edgetech systems number the low and
+    /// high frequency side scan subsystems 20 and 21. This is zero for
+    /// sonars with a single subsystem.
+    pub subsystem: u8,
     /// The ping data
     pub data: Vec<T>,
 }
@@ -49,6 +131,7 @@ impl<T> Ping<T> {
         frequency: f64,
         sampling_interval: f64,
         channel: Channel,
+        subsystem: u8,
         data: Vec<T>,
     ) -> Ping<T> {
         Ping {
@@ -57,6 +140,7 @@ impl<T> Ping<T> {
             frequency,
             sampling_interval,
             channel,
+            subsystem,
             data,
         }
     }
@@ -167,6 +251,86 @@ impl Course {
     }
 }
 
+/// A single bathymetric sounding
+///
+/// Systems report soundings either as a travel time and angle, which must
+/// be ray traced to find the position of the seafloor, or as a depth and
+/// horizontal offsets that have already been computed. Fields that are
+/// not reported are `None`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct Sounding {
+    /// The two way travel time in seconds
+    pub travel_time: Option<f64>,
+    /// The angle of arrival in degrees
+    ///
+    /// The reference direction of the angle depends on the sonar system.
+    pub angle: Option<f64>,
+    /// The depth below the sensor in meters
+    pub depth: Option<f64>,
+    /// The horizontal distance across track in meters, positive to starboard
+    pub across_track: Option<f64>,
+    /// The horizontal distance along track in meters, positive forward
+    pub along_track: Option<f64>,
+    /// The amplitude of the return
+    pub amplitude: Option<f64>,
+}
+
+/// The soundings of a swath bathymetry ping
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Bathymetry {
+    /// The source of the bathymetry
+    pub source: String,
+    /// The time at which the ping was acquired
+    #[serde(with = "time::serde::timestamp")]
+    pub timestamp: OffsetDateTime,
+    /// The channel (Port, Starboard, Other)
+    pub channel: Channel,
+    /// The soundings of the ping
+    pub soundings: Vec<Sounding>,
+}
+
+impl Bathymetry {
+    /// Create a new Bathymetry from the given data
+    pub fn new(
+        source: String,
+        timestamp: OffsetDateTime,
+        channel: Channel,
+        soundings: Vec<Sounding>,
+    ) -> Bathymetry {
+        Bathymetry {
+            source,
+            timestamp,
+            channel,
+            soundings,
+        }
+    }
+}
+
+/// The offsets of a sonar from the navigation reference point of a vessel
+///
+/// Positions and orientations are reported for the navigation reference
+/// point. These lever arms and angular offsets describe where the sonar
+/// is mounted relative to that point, so that the position and attitude
+/// of the sonar itself can be computed. SDW does not apply them to the
+/// records that it reads.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, Serialize)]
+pub struct NavigationOffsets {
+    /// The distance of the sonar forward of the reference point in meters
+    pub forward: f64,
+    /// The distance of the sonar to starboard of the reference point in meters
+    pub starboard: f64,
+    /// The distance of the sonar below the reference point in meters
+    pub down: f64,
+    /// The heading of the sonar relative to the vessel in degrees
+    pub heading: f64,
+    /// The pitch of the sonar relative to the vessel in degrees
+    pub pitch: f64,
+    /// The roll of the sonar relative to the vessel in degrees
+    pub roll: f64,
+    /// The elevation of the tow point of a towed sonar in meters
+    pub tow_point_elevation: f64,
+}
+
 /// A SonarDataRecord encapsulates the data available to SDW
 #[derive(Debug, Deserialize, Serialize)]
 pub enum SonarDataRecord<T> {
@@ -178,6 +342,22 @@ pub enum SonarDataRecord<T> {
     Orientation(Orientation),
     /// A wrapper for a Course
     Course(Course),
+    /// A wrapper for a Bathymetry
+    Bathymetry(Bathymetry),
     /// An unknown data type used as a catchall
     Unknown,
 }
+
+impl<T> SonarDataRecord<T> {
+    /// Return the kind of the record
+    pub fn kind(&self) -> RecordKind {
+        match self {
+            SonarDataRecord::Ping(_) => RecordKind::Ping,
+            SonarDataRecord::Position(_) => RecordKind::Position,
+            SonarDataRecord::Orientation(_) => RecordKind::Orientation,
+            SonarDataRecord::Course(_) => RecordKind::Course,
+            SonarDataRecord::Bathymetry(_) => RecordKind::Bathymetry,
+            SonarDataRecord::Unknown => RecordKind::Unknown,
+        }
+    }
+}
@@ -90,11 +90,26 @@ impl From<Shot> for SonarDataRecord<u8> {
             shot.frequency(),
             shot.sampling_interval(),
             Channel::Other,
+            0,
             shot.echo_data,
         ))
     }
 }
 
+impl From<Shot> for SonarDataRecord<f32> {
+    fn from(shot: Shot) -> Self {
+        SonarDataRecord::Ping(crate::model::Ping::new(
+            "unknown".to_string(),
+            shot.timestamp(),
+            shot.frequency(),
+            shot.sampling_interval(),
+            Channel::Other,
+            0,
+            shot.echo_data.iter().map(|&x| f32::from(x)).collect(),
+        ))
+    }
+}
+
 /// An iterator interface to an Imagenex .81b file
 pub struct File<T: io::Read + io::Seek> {
     /// The reader from which bytes are read and parsed
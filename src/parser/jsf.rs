@@ -1,15 +1,20 @@
 //! Parsing Edgetech JSF files
-use crate::model::{Channel, SonarDataRecord};
+use crate::model::{Channel, SonarDataRecord, Sounding};
+use crate::parser::nmea::{self, ParseSentenceError, Sentence, SentenceData};
 use binrw::io;
-use binrw::{binread, BinRead, BinResult};
+use binrw::{binrw, BinRead, BinResult, BinWrite};
+use num_complex::Complex;
 
-use time::{Duration, OffsetDateTime};
+use time::{Date, Duration, OffsetDateTime, PrimitiveDateTime, Time};
 
-use std::io::Seek;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::Range;
 
 /// A struct representing a message in a JSF file
-#[binread]
-#[br(little, magic = b"\x01\x16")]
+#[binrw]
+#[brw(little, magic = b"\x01\x16")]
 #[derive(Debug, PartialEq)]
 pub struct Message {
     protocol: u8,
@@ -18,21 +23,230 @@ pub struct Message {
     command_type: u8,
     subsystem_number: u8,
     channel_number: u8,
-    #[br(pad_after = 2)]
+    #[brw(pad_after = 2)]
     sequence_number: u8,
+    #[br(assert(
+        message_size >= body_size(message_type).unwrap_or(0),
+        "message size {} is too small for message type {}",
+        message_size,
+        message_type
+    ))]
+    #[bw(assert(
+        *message_size >= body_size(*message_type).unwrap_or(0),
+        "message size {} is too small for message type {}",
+        message_size,
+        message_type
+    ))]
     message_size: i32,
     #[br(args {message_size, message_type})]
+    #[bw(args {message_size: *message_size, message_type: *message_type})]
     data: MessageType,
 }
 
 impl Message {
-    fn channel(&self) -> Channel {
+    /// Return the protocol version of the message
+    pub fn protocol(&self) -> u8 {
+        self.protocol
+    }
+
+    /// Return the session identifier
+    pub fn session_identifier(&self) -> u8 {
+        self.session_identifier
+    }
+
+    /// Return the message type
+    pub fn message_type(&self) -> u16 {
+        self.message_type
+    }
+
+    /// Return the command type
+    pub fn command_type(&self) -> u8 {
+        self.command_type
+    }
+
+    /// Return the subsystem number
+    ///
+    /// For sonar data, 0 is the sub-bottom profiler, and 20 and 21 are
+    /// the low and high frequencies of a side scan sonar.
+    pub fn subsystem_number(&self) -> u8 {
+        self.subsystem_number
+    }
+
+    /// Return the channel number
+    ///
+    /// For side scan sonar data, 0 is port and 1 is starboard.
+    pub fn channel_number(&self) -> u8 {
+        self.channel_number
+    }
+
+    /// Return the sequence number
+    pub fn sequence_number(&self) -> u8 {
+        self.sequence_number
+    }
+
+    /// Return the size of the message body in bytes
+    pub fn message_size(&self) -> i32 {
+        self.message_size
+    }
+
+    /// Return the channel of the message
+    pub fn channel(&self) -> Channel {
         match self.channel_number {
             0 => Channel::Port,
             1 => Channel::Starboard,
             _ => Channel::Other,
         }
     }
+
+    /// Return true if the message type is not parsed by SDW
+    ///
+    /// This includes private message types emitted by the system. The
+    /// bodies of these messages are kept as bytes, and are written back
+    /// unchanged by a [`Writer`].
+    pub fn is_unknown(&self) -> bool {
+        matches!(self.data, MessageType::M0 { .. })
+    }
+
+    /// Return the body of a system information message (type 182)
+    pub fn system_information(&self) -> Option<&SystemInformation> {
+        match &self.data {
+            MessageType::M182 { msg } => Some(msg),
+            _ => None,
+        }
+    }
+
+    /// Return the body of a navigation offsets message (type 181)
+    pub fn navigation_offsets(&self) -> Option<&NavigationOffsets> {
+        match &self.data {
+            MessageType::M181 { msg } => Some(msg),
+            _ => None,
+        }
+    }
+
+    /// Return the body of a sonar data message (type 80)
+    pub fn sonar_data(&self) -> Option<&SonarData> {
+        match &self.data {
+            MessageType::M80 { msg } => Some(msg),
+            _ => None,
+        }
+    }
+
+    /// Return the body of a sonar data message (type 80) for editing
+    pub fn sonar_data_mut(&mut self) -> Option<&mut SonarData> {
+        match &mut self.data {
+            MessageType::M80 { msg } => Some(msg),
+            _ => None,
+        }
+    }
+
+    /// Return the body of an NMEA string message (type 2002)
+    pub fn nmea_string(&self) -> Option<&NMEAString> {
+        match &self.data {
+            MessageType::M2002 { msg } => Some(msg),
+            _ => None,
+        }
+    }
+
+    /// Replace the sentence of an NMEA string message (type 2002)
+    ///
+    /// The message size is updated to fit the new sentence. Returns false,
+    /// and leaves the message unchanged, if it is not an NMEA string
+    /// message.
+    pub fn set_nmea_sentence(&mut self, sentence: &str) -> bool {
+        match &mut self.data {
+            MessageType::M2002 { msg } => {
+                msg.data = sentence.as_bytes().to_vec();
+                self.message_size = 12 + msg.data.len() as i32;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Return the body of a pitch-roll data message (type 2020)
+    pub fn pitch_roll_data(&self) -> Option<&PitchRollData> {
+        match &self.data {
+            MessageType::M2020 { msg } => Some(msg),
+            _ => None,
+        }
+    }
+
+    /// Return the body of a pitch-roll data message (type 2020) for editing
+    pub fn pitch_roll_data_mut(&mut self) -> Option<&mut PitchRollData> {
+        match &mut self.data {
+            MessageType::M2020 { msg } => Some(msg),
+            _ => None,
+        }
+    }
+
+    /// Return the navigation records that accompany the record of a message
+    ///
+    /// Some messages carry more than one kind of navigation data, while
+    /// `SonarDataRecord::from` converts a message to a single record.
+    /// The other kinds are returned here, with the timestamp of the
+    /// message. Only the fields that are flagged as valid are used, and
+    /// a record is only returned if it has at least one valid field.
+    ///
+    /// Sonar data messages (type 80) carry the position, attitude and
+    /// course of the sonar at the time of the ping. These are returned
+    /// as `Position`, `Orientation` and `Course` records, so that files
+    /// without separate navigation messages can still be georeferenced.
+    /// A `Position` is only returned for geographic coordinates. The port
+    /// and starboard messages of a ping carry the same navigation data,
+    /// so records are only returned for channel 0.
+    ///
+    /// Situation messages (types 2090 and 2091) are converted to a
+    /// `Position`, and their attitude is returned as an `Orientation`.
+    /// Doppler velocity log messages (type 2080) are converted to an
+    /// `Orientation`, and their velocity over the bottom is returned as a
+    /// `Course` if it is in earth coordinates.
+    pub fn companion_records<T>(&self) -> Vec<SonarDataRecord<T>> {
+        let records = match &self.data {
+            MessageType::M80 { msg: mt } if self.channel_number == 0 => {
+                let timestamp = match mt.epoch_timestamp() {
+                    Ok(timestamp) => timestamp,
+                    Err(_) => return Vec::new(),
+                };
+                let position = match mt.coordinates() {
+                    Some(Coordinates::Geographic {
+                        longitude,
+                        latitude,
+                    }) => position(timestamp, Some(longitude), Some(latitude), mt.depth()),
+                    _ => SonarDataRecord::Unknown,
+                };
+                vec![
+                    position,
+                    orientation(timestamp, mt.pitch(), mt.roll(), mt.heading()),
+                    course(timestamp, mt.speed(), mt.course()),
+                ]
+            }
+            MessageType::M2080 { msg: mt } => {
+                let (speed, track) = mt.course().unzip();
+                vec![course(mt.timestamp(), speed, track)]
+            }
+            MessageType::M2090 { msg: mt } => {
+                vec![orientation(
+                    mt.timestamp(),
+                    mt.pitch(),
+                    mt.roll(),
+                    mt.heading(),
+                )]
+            }
+            MessageType::M2091 { msg: mt } => {
+                vec![orientation(
+                    mt.timestamp(),
+                    mt.pitch(),
+                    mt.roll(),
+                    mt.heading(),
+                )]
+            }
+            _ => Vec::new(),
+        };
+        records
+            .into_iter()
+            .filter(|rec| !matches!(rec, SonarDataRecord::Unknown))
+            .collect()
+    }
 }
 
 /// An unknown message type
@@ -40,8 +254,8 @@ impl Message {
 /// This simply wraps the byte data of the message.
 /// It is used to represent both private message types
 /// emitted by the system and unimplemented messages.
-#[binread]
-#[br(little,import {message_size:i32})]
+#[binrw]
+#[brw(little,import {message_size:i32})]
 #[derive(Debug, PartialEq)]
 pub struct UnknownMessage {
     #[br(count=message_size)]
@@ -49,8 +263,8 @@ pub struct UnknownMessage {
 }
 
 /// The system information message
-#[binread]
-#[br(little,import {message_size:i32})]
+#[binrw]
+#[brw(little,import {message_size:i32})]
 #[derive(Debug, PartialEq)]
 pub struct SystemInformation {
     system_type: i32,
@@ -58,13 +272,50 @@ pub struct SystemInformation {
     version_number: i32,
     n_subsystems: i32,
     n_serial_ports: i32,
-    #[br(pad_after=message_size-24)]
+    #[brw(pad_after=message_size-24)]
     serial_number: i32,
 }
 
+impl SystemInformation {
+    /// Return the system type
+    ///
+    /// This identifies the model of the topside or sonar, such as 128 for
+    /// a 4300-MPX or 14 for a 3300-HM.
+    pub fn system_type(&self) -> i32 {
+        self.system_type
+    }
+
+    /// Return the low rate I/O enabled option
+    ///
+    /// This is 0 when disabled.
+    pub fn low_rate_io(&self) -> i32 {
+        self.low_rate_io
+    }
+
+    /// Return the version number of the sonar software
+    pub fn version_number(&self) -> i32 {
+        self.version_number
+    }
+
+    /// Return the number of subsystems present
+    pub fn n_subsystems(&self) -> i32 {
+        self.n_subsystems
+    }
+
+    /// Return the number of serial port devices present
+    pub fn n_serial_ports(&self) -> i32 {
+        self.n_serial_ports
+    }
+
+    /// Return the serial number of the towed vehicle
+    pub fn serial_number(&self) -> i32 {
+        self.serial_number
+    }
+}
+
 /// The navigation offsets message
-#[binread]
-#[br(little)]
+#[binrw]
+#[brw(little)]
 #[derive(Debug, PartialEq)]
 pub struct NavigationOffsets {
     x: f32,
@@ -79,32 +330,207 @@ pub struct NavigationOffsets {
     pitch: f32,
     roll: f32,
     yaw: f32,
-    #[br(pad_after = 12)]
+    #[brw(pad_after = 12)]
     tow_point_elevation: f32,
 }
 
+impl NavigationOffsets {
+    /// Return the X offset of the navigation reference in meters
+    pub fn x(&self) -> f64 {
+        f64::from(self.x)
+    }
+
+    /// Return the Y offset of the navigation reference in meters
+    pub fn y(&self) -> f64 {
+        f64::from(self.y)
+    }
+
+    /// Return the latitude offset in degrees
+    pub fn latitude(&self) -> f64 {
+        f64::from(self.latitude)
+    }
+
+    /// Return the longitude offset in degrees
+    pub fn longitude(&self) -> f64 {
+        f64::from(self.longitude)
+    }
+
+    /// Return the offset of the sonar aft of the navigation reference in meters
+    pub fn aft(&self) -> f64 {
+        f64::from(self.aft)
+    }
+
+    /// Return the offset of the sonar to starboard of the navigation reference in meters
+    pub fn starboard(&self) -> f64 {
+        f64::from(self.starboard)
+    }
+
+    /// Return the depth offset in meters
+    pub fn depth(&self) -> f64 {
+        f64::from(self.depth)
+    }
+
+    /// Return the altitude offset in meters
+    pub fn altitude(&self) -> f64 {
+        f64::from(self.altitude)
+    }
+
+    /// Return the heading offset in degrees
+    pub fn heading(&self) -> f64 {
+        f64::from(self.heading)
+    }
+
+    /// Return the pitch offset in degrees
+    pub fn pitch(&self) -> f64 {
+        f64::from(self.pitch)
+    }
+
+    /// Return the roll offset in degrees
+    pub fn roll(&self) -> f64 {
+        f64::from(self.roll)
+    }
+
+    /// Return the yaw offset in degrees
+    pub fn yaw(&self) -> f64 {
+        f64::from(self.yaw)
+    }
+
+    /// Return the elevation of the tow point in meters
+    pub fn tow_point_elevation(&self) -> f64 {
+        f64::from(self.tow_point_elevation)
+    }
+}
+
+impl From<&NavigationOffsets> for crate::model::NavigationOffsets {
+    fn from(offsets: &NavigationOffsets) -> Self {
+        crate::model::NavigationOffsets {
+            forward: -offsets.aft(),
+            starboard: offsets.starboard(),
+            down: offsets.depth(),
+            heading: offsets.heading(),
+            pitch: offsets.pitch(),
+            roll: offsets.roll(),
+            tow_point_elevation: offsets.tow_point_elevation(),
+        }
+    }
+}
+
+/// The time basis of the calendar fields of a sonar data message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeBasis {
+    /// Coordinated universal time
+    Utc,
+    /// The local time of the topside
+    Local,
+    /// A time basis that is not recognized
+    Unknown(i16),
+}
+
+impl From<i16> for TimeBasis {
+    fn from(time_basis: i16) -> Self {
+        match time_basis {
+            0 => TimeBasis::Utc,
+            1 => TimeBasis::Local,
+            x => TimeBasis::Unknown(x),
+        }
+    }
+}
+
+/// An error in the timestamp of a sonar data message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimestampError {
+    /// The time since the epoch cannot be represented
+    InvalidTime(i32),
+    /// The milliseconds today are a day or more
+    InvalidMilliseconds(u32),
+    /// The calendar fields do not form a valid date and time
+    InvalidCalendar,
+    /// The calendar time does not agree with the time since the epoch
+    CalendarMismatch {
+        /// The time since the epoch
+        time: OffsetDateTime,
+        /// The calendar date and time
+        calendar: PrimitiveDateTime,
+    },
+    /// The milliseconds today do not agree with the calendar time of day
+    MillisecondsMismatch {
+        /// The milliseconds today
+        milliseconds_today: u32,
+        /// The calendar date and time
+        calendar: PrimitiveDateTime,
+    },
+}
+
+impl fmt::Display for TimestampError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimestampError::InvalidTime(time) => write!(f, "Invalid time: {}", time),
+            TimestampError::InvalidMilliseconds(ms) => {
+                write!(f, "Invalid milliseconds today: {}", ms)
+            }
+            TimestampError::InvalidCalendar => write!(f, "Invalid calendar date and time"),
+            TimestampError::CalendarMismatch { time, calendar } => {
+                write!(f, "Calendar time {} does not match time {}", calendar, time)
+            }
+            TimestampError::MillisecondsMismatch {
+                milliseconds_today,
+                calendar,
+            } => write!(
+                f,
+                "Milliseconds today {} do not match calendar time {}",
+                milliseconds_today, calendar
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TimestampError {}
+
+/// An error when the trace of a sonar data message does not fit its format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SampleSizeError {
+    /// The format of the samples
+    pub data_format: DataFormat,
+    /// The number of samples in the header
+    pub samples: u16,
+    /// The size of the trace in bytes
+    pub size: usize,
+}
+
+impl fmt::Display for SampleSizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} samples of {:?} data do not fit in {} bytes",
+            self.samples, self.data_format, self.size
+        )
+    }
+}
+
+impl std::error::Error for SampleSizeError {}
+
 /// The sonar data message
-#[binread]
-#[br(little,import {message_size:i32})]
+#[binrw]
+#[brw(little,import {message_size:i32})]
 #[derive(Debug, PartialEq)]
 pub struct SonarData {
     time: i32,
     starting_depth: u32,
-    #[br(pad_after = 4)]
+    #[brw(pad_after = 4)]
     ping_number: u32,
     msbs: u16,
     lsb1: u16,
-    #[br(pad_after = 6)]
+    #[brw(pad_after = 6)]
     lsb2: u16,
     id_code: i16,
-    #[br(pad_after = 2)]
+    #[brw(pad_after = 2)]
     validity_flag: u16,
     data_format: i16,
     aft_antenna_distance: i16,
-    #[br(pad_after = 4)]
+    #[brw(pad_after = 4)]
     starboard_antenna_distance: i16,
     km_pipe: f32,
-    #[br(pad_after = 24)]
+    #[brw(pad_after = 24)]
     heave: f32,
     gap_filler_lateral_offset: f32,
     x_position: i32,
@@ -115,7 +541,7 @@ pub struct SonarData {
     samples: u16,
     sampling_interval: u32,
     adc_gain_factor: u16,
-    #[br(pad_after = 2)]
+    #[brw(pad_after = 2)]
     transmit_level: i16,
     start_frequency: u16,
     end_frequency: u16,
@@ -137,9 +563,9 @@ pub struct SonarData {
     number_of_pulses: i16,
     heading: u16,
     pitch: i16,
-    #[br(pad_after = 2)]
+    #[brw(pad_after = 2)]
     roll: i16,
-    #[br(pad_before = 2)]
+    #[brw(pad_before = 2)]
     trigger_source: i16,
     mark_number: i16,
     position_fix_hour: i16,
@@ -150,165 +576,756 @@ pub struct SonarData {
     position_fix_day: i16,
     position_fix_year: i16,
     milliseconds_today: u32,
-    #[br(pad_after = 4)]
+    #[brw(pad_after = 4)]
     max_adc_value: u16,
     #[br(count = 6)]
     software_version_number: Vec<u8>,
     spherical_correction_factor: i32,
     packet_number: u16,
-    #[br(pad_after = 2)]
+    #[brw(pad_after = 2)]
     adc_decimation: i16,
     temperature: i16,
-    #[br(pad_after = 4)]
+    #[brw(pad_after = 4)]
     layback: f32,
-    #[br(pad_after = 2)]
+    #[brw(pad_after = 2)]
     cable_out: u16,
-    #[br(count=(message_size-240)>>1)]
-    trace: Vec<u16>,
+    #[br(count = message_size - 240)]
+    trace: Vec<u8>,
 }
 
-impl SonarData {
-    /// Return the timestamp
-    pub fn timestamp(&self) -> OffsetDateTime {
-        OffsetDateTime::from_unix_timestamp(i64::from(self.time)).unwrap()
-            + Duration::milliseconds(i64::from(self.milliseconds_today % 1000))
-    }
-
-    /// Return the mixer frequency in hertz
-    pub fn mixer_frequency(&self) -> f64 {
-        f64::from(self.mixer_frequency)
-    }
+/// The format of the samples in a sonar data message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataFormat {
+    /// Envelope data, one unsigned value per sample
+    Envelope,
+    /// Analytic signal, a real and an imaginary value per sample
+    Analytic,
+    /// Raw data before matched filtering, one value per sample
+    Raw,
+    /// The real part of the analytic signal, one value per sample
+    Real,
+    /// Pixel data, one unsigned value per sample
+    Pixel,
+    /// Analytic signal of the raw data (format 9), a real and an
+    /// imaginary value per sample
+    ///
+    /// This is the complex counterpart of [`DataFormat::Raw`]: it is
+    /// recorded before the matched filter, so pulse compression must
+    /// still be applied to it.
+    RawAnalytic,
+    /// Analytic signal recorded without a matched filter (format 11), a
+    /// real and an imaginary value per sample
+    ///
+    /// Unlike [`DataFormat::RawAnalytic`], this is the processed signal of
+    /// [`DataFormat::Analytic`] with only the matched filter left out.
+    /// SDW decodes both formats in the same way.
+    UnmatchedAnalytic,
+    /// A format that is not recognized
+    Unknown(i16),
+}
 
-    /// Return the sampling interval in seconds
-    pub fn sampling_interval(&self) -> f64 {
-        1e-9 * f64::from(self.sampling_interval)
+impl DataFormat {
+    /// Return the number of bytes of each sample
+    ///
+    /// Every value is a 16-bit integer, so complex samples take four bytes
+    /// and all other samples two.
+    pub fn sample_size(&self) -> usize {
+        match self {
+            DataFormat::Analytic | DataFormat::RawAnalytic | DataFormat::UnmatchedAnalytic => 4,
+            _ => 2,
+        }
     }
+}
 
-    /// Return the sonar data trace
-    pub fn trace(&self) -> Vec<f32> {
-        let scale: f32 = 2.0f32.powi(-(i32::from(self.weighting_factor)));
-        self.trace.iter().map(|&x| f32::from(x) * scale).collect()
+impl From<i16> for DataFormat {
+    fn from(data_format: i16) -> Self {
+        match data_format {
+            0 => DataFormat::Envelope,
+            1 => DataFormat::Analytic,
+            2 => DataFormat::Raw,
+            3 => DataFormat::Real,
+            4 => DataFormat::Pixel,
+            9 => DataFormat::RawAnalytic,
+            11 => DataFormat::UnmatchedAnalytic,
+            n => DataFormat::Unknown(n),
+        }
     }
 }
 
-/// The NMEA string message
-#[binread]
-#[br(little,import {message_size:i32})]
-#[derive(Debug, PartialEq)]
-pub struct NMEAString {
-    time: i32,
-    milliseconds: i32,
-    #[br(pad_after = 3)]
-    source: u8,
-    #[br(count=message_size-12)]
-    data: Vec<u8>,
+/// The decoded samples of a sonar data message
+///
+/// Samples are scaled by the weighting factor of the message.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Samples {
+    /// Unsigned magnitudes, from envelope and pixel data
+    Magnitude(Vec<f32>),
+    /// Signed real values, from raw data and the real part of the analytic signal
+    Real(Vec<f32>),
+    /// Complex values, from analytic data
+    Complex(Vec<Complex<f32>>),
 }
 
-/// The pitch-roll data message
-#[binread]
-#[br(little)]
-#[derive(Debug, PartialEq)]
-pub struct PitchRollData {
-    time: i32,
-    #[br(pad_after = 4)]
-    milliseconds: i32,
-    acceleration_x: i16,
-    acceleration_y: i16,
-    acceleration_z: i16,
-    gyro_rate_x: i16,
-    gyro_rate_y: i16,
-    gyro_rate_z: i16,
-    pitch: i16,
-    roll: i16,
-    temperature: i16,
-    device_info: u16,
-    heave: i16,
-    heading: u16,
-    validity_flag: i32,
-    #[br(pad_after = 2)]
-    yaw: i16,
+/// The position of a sonar in a sonar data message
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Coordinates {
+    /// A longitude and latitude in degrees
+    Geographic {
+        /// The longitude in degrees
+        longitude: f64,
+        /// The latitude in degrees
+        latitude: f64,
+    },
+    /// A projected position in meters
+    Projected {
+        /// The easting (X) in meters
+        easting: f64,
+        /// The northing (Y) in meters
+        northing: f64,
+    },
 }
 
-impl PitchRollData {
-    /// Return the timestamp
-    pub fn timestamp(&self) -> OffsetDateTime {
-        OffsetDateTime::from_unix_timestamp(i64::from(self.time)).unwrap()
-            + Duration::milliseconds(i64::from(self.milliseconds % 1000))
+impl SonarData {
+    fn is_position_valid(&self) -> bool {
+        self.validity_flag & 0x0001 != 0
     }
 
-    fn is_acceleration_x_valid(&self) -> bool {
-        self.validity_flag & 0x0001 == 1
+    fn is_course_valid(&self) -> bool {
+        self.validity_flag & 0x0002 != 0
     }
 
-    fn is_acceleration_y_valid(&self) -> bool {
-        ((self.validity_flag & 0x0002) >> 1) == 1
+    fn is_speed_valid(&self) -> bool {
+        self.validity_flag & 0x0004 != 0
     }
 
-    fn is_acceleration_z_valid(&self) -> bool {
-        ((self.validity_flag & 0x0004) >> 2) == 1
+    fn is_heading_valid(&self) -> bool {
+        self.validity_flag & 0x0008 != 0
     }
 
-    fn is_gyro_rate_x_valid(&self) -> bool {
-        ((self.validity_flag & 0x0008) >> 3) == 1
+    fn is_pitch_roll_valid(&self) -> bool {
+        self.validity_flag & 0x0020 != 0
     }
 
-    fn is_gyro_rate_y_valid(&self) -> bool {
-        ((self.validity_flag & 0x0010) >> 4) == 1
+    fn is_altitude_valid(&self) -> bool {
+        self.validity_flag & 0x0040 != 0
     }
 
-    fn is_gyro_rate_z_valid(&self) -> bool {
-        ((self.validity_flag & 0x0020) >> 5) == 1
+    fn is_depth_valid(&self) -> bool {
+        self.validity_flag & 0x0200 != 0
     }
 
-    fn is_pitch_valid(&self) -> bool {
-        ((self.validity_flag & 0x0040) >> 6) == 1
+    fn set_valid(&mut self, mask: u16, valid: bool) {
+        if valid {
+            self.validity_flag |= mask;
+        } else {
+            self.validity_flag &= !mask;
+        }
     }
 
-    fn is_roll_valid(&self) -> bool {
-        ((self.validity_flag & 0x0080) >> 7) == 1
+    /// Set the position of the sonar
+    ///
+    /// Geographic coordinates are stored in ten thousandths of a minute of
+    /// arc. Projected coordinates are stored in the units of the message if
+    /// it already holds a projected position, and in centimeters otherwise.
+    /// The position is marked invalid if it is `None`.
+    pub fn set_coordinates(&mut self, coordinates: Option<Coordinates>) {
+        match coordinates {
+            Some(Coordinates::Geographic {
+                longitude,
+                latitude,
+            }) => {
+                self.x_position = (longitude * 60.0 * 10000.0).round() as i32;
+                self.y_position = (latitude * 60.0 * 10000.0).round() as i32;
+                self.coordinate_units = 2;
+            }
+            Some(Coordinates::Projected { easting, northing }) => {
+                let (units, scale) = match self.coordinate_units {
+                    1 => (1, 1e-3),
+                    3 => (3, 1e-1),
+                    _ => (4, 1e-2),
+                };
+                self.x_position = (easting / scale).round() as i32;
+                self.y_position = (northing / scale).round() as i32;
+                self.coordinate_units = units;
+            }
+            None => {}
+        }
+        self.set_valid(0x0001, coordinates.is_some());
     }
 
-    fn is_heave_valid(&self) -> bool {
-        ((self.validity_flag & 0x0100) >> 8) == 1
+    /// Set the course in degrees east of North
+    ///
+    /// The course is stored in whole degrees, and is marked invalid if it
+    /// is `None`.
+    pub fn set_course(&mut self, course: Option<f64>) {
+        if let Some(course) = course {
+            self.course = course.rem_euclid(360.0).round() as i16;
+        }
+        self.set_valid(0x0002, course.is_some());
     }
 
-    fn is_heading_valid(&self) -> bool {
-        ((self.validity_flag & 0x0200) >> 9) == 1
+    /// Set the speed in m/s
+    ///
+    /// The speed is stored in tenths of a knot, and is marked invalid if it
+    /// is `None`.
+    pub fn set_speed(&mut self, speed: Option<f64>) {
+        if let Some(speed) = speed {
+            self.speed = (speed * 3600.0 / 1852.0 * 10.0).round() as i16;
+        }
+        self.set_valid(0x0004, speed.is_some());
     }
 
-    fn is_temperature_valid(&self) -> bool {
-        ((self.validity_flag & 0x0400) >> 10) == 1
+    /// Set the heading in degrees east of North
+    ///
+    /// The heading is marked invalid if it is `None`.
+    pub fn set_heading(&mut self, heading: Option<f64>) {
+        if let Some(heading) = heading {
+            self.heading = (heading.rem_euclid(360.0) * 100.0).round() as u16;
+        }
+        self.set_valid(0x0008, heading.is_some());
     }
 
-    fn is_device_info_valid(&self) -> bool {
-        ((self.validity_flag & 0x0800) >> 11) == 1
+    /// Set the pitch and roll in degrees
+    ///
+    /// Bow up and port up are positive. The pitch and roll are marked
+    /// invalid if they are `None`.
+    pub fn set_pitch_roll(&mut self, pitch_roll: Option<(f64, f64)>) {
+        if let Some((pitch, roll)) = pitch_roll {
+            self.pitch = (pitch * 32768.0 / 180.0).round() as i16;
+            self.roll = (roll * 32768.0 / 180.0).round() as i16;
+        }
+        self.set_valid(0x0020, pitch_roll.is_some());
     }
 
-    fn is_yaw_valid(&self) -> bool {
-        ((self.validity_flag & 0x1000) >> 12) == 1
+    /// Set the altitude of the sonar above the seafloor in meters
+    ///
+    /// The altitude is marked invalid if it is `None`.
+    pub fn set_altitude(&mut self, altitude: Option<f64>) {
+        if let Some(altitude) = altitude {
+            self.altitude = (altitude * 1000.0).round() as i32;
+        }
+        self.set_valid(0x0040, altitude.is_some());
     }
 
-    /// Compute the acceleration
+    /// Set the depth of the sonar below the surface in meters
     ///
-    /// The result is the acceleration in the (x,y,z) direction
-    pub fn acceleration(&self) -> Option<(f64, f64, f64)> {
-        if self.is_acceleration_x_valid()
-            && self.is_acceleration_y_valid()
-            && self.is_acceleration_z_valid()
-        {
-            Some((
-                f64::from(self.acceleration_x) * 20.0 * 1.5 / 32768.0,
-                f64::from(self.acceleration_y) * 20.0 * 1.5 / 32768.0,
-                f64::from(self.acceleration_z) * 20.0 * 1.5 / 32768.0,
-            ))
+    /// The depth is marked invalid if it is `None`.
+    pub fn set_depth(&mut self, depth: Option<f64>) {
+        if let Some(depth) = depth {
+            self.depth = (depth * 1000.0).round() as i32;
+        }
+        self.set_valid(0x0200, depth.is_some());
+    }
+
+    /// Return the position of the sonar
+    ///
+    /// The units of the position depend on `coordinate_units`. Geographic
+    /// coordinates are stored in ten thousandths of a minute of arc, and
+    /// projected coordinates in millimeters, centimeters or decimeters.
+    pub fn coordinates(&self) -> Option<Coordinates> {
+        if !self.is_position_valid() {
+            return None;
+        }
+        let x = f64::from(self.x_position);
+        let y = f64::from(self.y_position);
+        let projected = |scale: f64| Coordinates::Projected {
+            easting: x * scale,
+            northing: y * scale,
+        };
+        match self.coordinate_units {
+            1 => Some(projected(1e-3)),
+            2 => Some(Coordinates::Geographic {
+                longitude: x / 10000.0 / 60.0,
+                latitude: y / 10000.0 / 60.0,
+            }),
+            3 => Some(projected(1e-1)),
+            4 => Some(projected(1e-2)),
+            _ => None,
+        }
+    }
+
+    /// Return the course in degrees east of North
+    pub fn course(&self) -> Option<f64> {
+        if self.is_course_valid() {
+            Some(f64::from(self.course))
         } else {
             None
         }
     }
 
-    /// Compute the gyro rate in degrees/sec
+    /// Return the speed in m/s
     ///
-    /// The return is the gyro rate in the (x,y,z) direction    
+    /// The speed is stored in tenths of a knot.
+    pub fn speed(&self) -> Option<f64> {
+        if self.is_speed_valid() {
+            Some(f64::from(self.speed) * 0.1 * 1852.0 / 3600.0)
+        } else {
+            None
+        }
+    }
+
+    /// Return the heading in degrees east of North
+    pub fn heading(&self) -> Option<f64> {
+        if self.is_heading_valid() {
+            Some(f64::from(self.heading) * 0.01)
+        } else {
+            None
+        }
+    }
+
+    /// Return the pitch in degrees
+    ///
+    /// Bow up is positive
+    pub fn pitch(&self) -> Option<f64> {
+        if self.is_pitch_roll_valid() {
+            Some(f64::from(self.pitch) * 180.0 / 32768.0)
+        } else {
+            None
+        }
+    }
+
+    /// Return the roll in degrees
+    ///
+    /// Port up is positive
+    pub fn roll(&self) -> Option<f64> {
+        if self.is_pitch_roll_valid() {
+            Some(f64::from(self.roll) * 180.0 / 32768.0)
+        } else {
+            None
+        }
+    }
+
+    /// Return the altitude of the sonar above the seafloor in meters
+    pub fn altitude(&self) -> Option<f64> {
+        if self.is_altitude_valid() {
+            Some(f64::from(self.altitude) / 1000.0)
+        } else {
+            None
+        }
+    }
+
+    /// Return the depth of the sonar below the surface in meters
+    pub fn depth(&self) -> Option<f64> {
+        if self.is_depth_valid() {
+            Some(f64::from(self.depth) / 1000.0)
+        } else {
+            None
+        }
+    }
+
+    /// Return the layback of the sonar behind the vessel in meters
+    pub fn layback(&self) -> f64 {
+        f64::from(self.layback)
+    }
+
+    /// Return the time basis of the calendar fields
+    pub fn time_basis(&self) -> TimeBasis {
+        TimeBasis::from(self.time_basis)
+    }
+
+    /// Return the calendar date and time of the ping
+    ///
+    /// This is in the time basis of the message, and is `None` if the
+    /// calendar fields are not set.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if the fields are set but do not
+    /// form a valid date and time.
+    pub fn calendar(&self) -> Result<Option<PrimitiveDateTime>, TimestampError> {
+        if self.year == 0 {
+            return Ok(None);
+        }
+        let date = u16::try_from(self.day)
+            .ok()
+            .and_then(|day| Date::from_ordinal_date(i32::from(self.year), day).ok());
+        let time = match (
+            u8::try_from(self.hour),
+            u8::try_from(self.minute),
+            u8::try_from(self.second),
+        ) {
+            (Ok(hour), Ok(minute), Ok(second)) => Time::from_hms(hour, minute, second).ok(),
+            _ => None,
+        };
+        match (date, time) {
+            (Some(date), Some(time)) => Ok(Some(PrimitiveDateTime::new(date, time))),
+            _ => Err(TimestampError::InvalidCalendar),
+        }
+    }
+
+    /// Return the time since the epoch
+    ///
+    /// The whole seconds are taken from the time since the epoch, and the
+    /// milliseconds from the milliseconds today. This is the timestamp of
+    /// the records of the message, whether or not it agrees with the
+    /// calendar fields (see [`SonarData::timestamp`]).
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if the time since the epoch cannot
+    /// be represented.
+    pub fn epoch_timestamp(&self) -> Result<OffsetDateTime, TimestampError> {
+        let time = OffsetDateTime::from_unix_timestamp(i64::from(self.time))
+            .map_err(|_| TimestampError::InvalidTime(self.time))?;
+        Ok(time + Duration::milliseconds(i64::from(self.milliseconds_today % 1000)))
+    }
+
+    /// Return the timestamp, cross-checked against the calendar fields
+    ///
+    /// The whole seconds are taken from the time since the epoch, and the
+    /// milliseconds from the milliseconds today. When the calendar fields
+    /// are set, both are cross-checked against them. For a UTC time basis
+    /// the calendar time must agree with the time since the epoch. For
+    /// local time, or an unknown basis, they may differ by a time zone
+    /// offset of a whole number of quarter hours. The milliseconds today
+    /// must agree with the calendar time of day. A difference of up to a
+    /// second is allowed for rounding.
+    ///
+    /// A message that fails these checks is still converted to a record
+    /// with the time since the epoch. A [`File`] reports such messages
+    /// (see [`File::invalid_timestamps`]).
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if the milliseconds today are a day
+    /// or more, the calendar fields are invalid, or the time sources
+    /// disagree.
+    pub fn timestamp(&self) -> Result<OffsetDateTime, TimestampError> {
+        if self.milliseconds_today >= 86_400_000 {
+            return Err(TimestampError::InvalidMilliseconds(self.milliseconds_today));
+        }
+        let time = OffsetDateTime::from_unix_timestamp(i64::from(self.time))
+            .map_err(|_| TimestampError::InvalidTime(self.time))?;
+
+        if let Some(calendar) = self.calendar()? {
+            let offset = (calendar.assume_utc() - time).whole_seconds();
+            let zone = match self.time_basis() {
+                TimeBasis::Utc => 0,
+                TimeBasis::Local | TimeBasis::Unknown(_) => {
+                    (offset as f64 / 900.0).round() as i64 * 900
+                }
+            };
+            if (offset - zone).abs() > 1 || zone.abs() > 14 * 3600 {
+                return Err(TimestampError::CalendarMismatch { time, calendar });
+            }
+
+            let seconds_today = i64::from(self.milliseconds_today / 1000);
+            let calendar_today = i64::from(calendar.hour()) * 3600
+                + i64::from(calendar.minute()) * 60
+                + i64::from(calendar.second());
+            let difference = (seconds_today - calendar_today).rem_euclid(86_400);
+            if difference > 1 && difference < 86_399 {
+                return Err(TimestampError::MillisecondsMismatch {
+                    milliseconds_today: self.milliseconds_today,
+                    calendar,
+                });
+            }
+        }
+
+        Ok(time + Duration::milliseconds(i64::from(self.milliseconds_today % 1000)))
+    }
+
+    /// Return the mixer frequency in hertz
+    pub fn mixer_frequency(&self) -> f64 {
+        f64::from(self.mixer_frequency)
+    }
+
+    /// Return the sampling interval in seconds
+    pub fn sampling_interval(&self) -> f64 {
+        1e-9 * f64::from(self.sampling_interval)
+    }
+
+    /// Return the format of the samples
+    pub fn data_format(&self) -> DataFormat {
+        DataFormat::from(self.data_format)
+    }
+
+    /// Decode the samples according to the data format
+    ///
+    /// Values are little-endian 16-bit integers. Data in an unknown format
+    /// are decoded as unsigned magnitudes. An error is returned if the
+    /// size of the trace does not match the number of samples in the
+    /// format.
+    pub fn samples(&self) -> Result<Samples, SampleSizeError> {
+        let data_format = self.data_format();
+        if self.trace.len() != usize::from(self.samples) * data_format.sample_size() {
+            return Err(SampleSizeError {
+                data_format,
+                samples: self.samples,
+                size: self.trace.len(),
+            });
+        }
+
+        let scale: f32 = 2.0f32.powi(-(i32::from(self.weighting_factor)));
+        let unsigned = || {
+            self.trace
+                .chunks_exact(2)
+                .map(|b| f32::from(u16::from_le_bytes([b[0], b[1]])) * scale)
+        };
+        let signed = || {
+            self.trace
+                .chunks_exact(2)
+                .map(|b| f32::from(i16::from_le_bytes([b[0], b[1]])) * scale)
+        };
+
+        Ok(match data_format {
+            DataFormat::Envelope | DataFormat::Pixel | DataFormat::Unknown(_) => {
+                Samples::Magnitude(unsigned().collect())
+            }
+            DataFormat::Raw | DataFormat::Real => Samples::Real(signed().collect()),
+            DataFormat::Analytic | DataFormat::RawAnalytic | DataFormat::UnmatchedAnalytic => {
+                let values: Vec<f32> = signed().collect();
+                Samples::Complex(
+                    values
+                        .chunks_exact(2)
+                        .map(|v| Complex::new(v[0], v[1]))
+                        .collect(),
+                )
+            }
+        })
+    }
+
+    /// Return the sonar data trace
+    ///
+    /// Complex samples are converted to their magnitudes. Use
+    /// [`SonarData::samples`] to keep the phase.
+    pub fn trace(&self) -> Result<Vec<f32>, SampleSizeError> {
+        Ok(match self.samples()? {
+            Samples::Magnitude(trace) | Samples::Real(trace) => trace,
+            Samples::Complex(trace) => trace.iter().map(|x| x.norm()).collect(),
+        })
+    }
+}
+
+/// The NMEA string message
+#[binrw]
+#[brw(little,import {message_size:i32})]
+#[derive(Debug, PartialEq)]
+pub struct NMEAString {
+    time: i32,
+    milliseconds: i32,
+    #[brw(pad_after = 3)]
+    source: u8,
+    #[br(count=message_size-12)]
+    data: Vec<u8>,
+}
+
+impl NMEAString {
+    /// Return the timestamp
+    pub fn timestamp(&self) -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(i64::from(self.time)).unwrap()
+            + Duration::milliseconds(i64::from(self.milliseconds % 1000))
+    }
+
+    /// Parse the NMEA sentence
+    ///
+    /// Only the first sentence is parsed if the message holds several.
+    pub fn sentence(&self) -> Result<Sentence, ParseSentenceError> {
+        let data = String::from_utf8_lossy(&self.data);
+        let line = data
+            .trim_end_matches('\0')
+            .lines()
+            .find(|line| !line.trim().is_empty())
+            .unwrap_or("");
+        nmea::parse(line)
+    }
+
+    /// Convert the sentence to a record
+    ///
+    /// Sentences that are invalid, have no fix or carry no position,
+    /// course or heading are converted to `SonarDataRecord::Unknown`.
+    fn record<T>(&self) -> SonarDataRecord<T> {
+        let sentence = match self.sentence() {
+            Ok(sentence) => sentence,
+            Err(_) => return SonarDataRecord::Unknown,
+        };
+        let position = |longitude, latitude, altitude| {
+            SonarDataRecord::Position(crate::model::Position::new(
+                "unknown".to_string(),
+                self.timestamp(),
+                longitude,
+                latitude,
+                altitude,
+            ))
+        };
+        let course = |speed, heading| {
+            SonarDataRecord::Course(crate::model::Course::new(
+                "unknown".to_string(),
+                self.timestamp(),
+                speed,
+                heading,
+            ))
+        };
+
+        match sentence.data {
+            SentenceData::Gga {
+                latitude,
+                longitude,
+                quality,
+                altitude,
+                ..
+            } if quality != 0 => position(longitude, latitude, altitude),
+            SentenceData::Gll {
+                latitude,
+                longitude,
+                valid: true,
+                ..
+            } => position(longitude, latitude, None),
+            SentenceData::Rmc {
+                latitude,
+                longitude,
+                valid: true,
+                ..
+            } => position(longitude, latitude, None),
+            SentenceData::Vtg { track, speed } => course(speed, track),
+            SentenceData::Hdt { heading } => course(None, heading),
+            _ => SonarDataRecord::Unknown,
+        }
+    }
+}
+
+/// The pitch-roll data message
+#[binrw]
+#[brw(little)]
+#[derive(Debug, PartialEq)]
+pub struct PitchRollData {
+    time: i32,
+    #[brw(pad_after = 4)]
+    milliseconds: i32,
+    acceleration_x: i16,
+    acceleration_y: i16,
+    acceleration_z: i16,
+    gyro_rate_x: i16,
+    gyro_rate_y: i16,
+    gyro_rate_z: i16,
+    pitch: i16,
+    roll: i16,
+    temperature: i16,
+    device_info: u16,
+    heave: i16,
+    heading: u16,
+    validity_flag: i32,
+    #[brw(pad_after = 2)]
+    yaw: i16,
+}
+
+impl PitchRollData {
+    /// Return the timestamp
+    pub fn timestamp(&self) -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(i64::from(self.time)).unwrap()
+            + Duration::milliseconds(i64::from(self.milliseconds % 1000))
+    }
+
+    fn is_acceleration_x_valid(&self) -> bool {
+        self.validity_flag & 0x0001 == 1
+    }
+
+    fn is_acceleration_y_valid(&self) -> bool {
+        ((self.validity_flag & 0x0002) >> 1) == 1
+    }
+
+    fn is_acceleration_z_valid(&self) -> bool {
+        ((self.validity_flag & 0x0004) >> 2) == 1
+    }
+
+    fn is_gyro_rate_x_valid(&self) -> bool {
+        ((self.validity_flag & 0x0008) >> 3) == 1
+    }
+
+    fn is_gyro_rate_y_valid(&self) -> bool {
+        ((self.validity_flag & 0x0010) >> 4) == 1
+    }
+
+    fn is_gyro_rate_z_valid(&self) -> bool {
+        ((self.validity_flag & 0x0020) >> 5) == 1
+    }
+
+    fn is_pitch_valid(&self) -> bool {
+        ((self.validity_flag & 0x0040) >> 6) == 1
+    }
+
+    fn is_roll_valid(&self) -> bool {
+        ((self.validity_flag & 0x0080) >> 7) == 1
+    }
+
+    fn is_heave_valid(&self) -> bool {
+        ((self.validity_flag & 0x0100) >> 8) == 1
+    }
+
+    fn is_heading_valid(&self) -> bool {
+        ((self.validity_flag & 0x0200) >> 9) == 1
+    }
+
+    fn is_temperature_valid(&self) -> bool {
+        ((self.validity_flag & 0x0400) >> 10) == 1
+    }
+
+    fn is_device_info_valid(&self) -> bool {
+        ((self.validity_flag & 0x0800) >> 11) == 1
+    }
+
+    fn is_yaw_valid(&self) -> bool {
+        ((self.validity_flag & 0x1000) >> 12) == 1
+    }
+
+    fn set_valid(&mut self, mask: i32, valid: bool) {
+        if valid {
+            self.validity_flag |= mask;
+        } else {
+            self.validity_flag &= !mask;
+        }
+    }
+
+    /// Set the pitch in degrees
+    ///
+    /// Bow up is positive. The pitch is marked invalid if it is `None`.
+    pub fn set_pitch(&mut self, pitch: Option<f64>) {
+        if let Some(pitch) = pitch {
+            self.pitch = (pitch * 32768.0 / 180.0).round() as i16;
+        }
+        self.set_valid(0x0040, pitch.is_some());
+    }
+
+    /// Set the roll in degrees
+    ///
+    /// Port up is positive. The roll is marked invalid if it is `None`.
+    pub fn set_roll(&mut self, roll: Option<f64>) {
+        if let Some(roll) = roll {
+            self.roll = (roll * 32768.0 / 180.0).round() as i16;
+        }
+        self.set_valid(0x0080, roll.is_some());
+    }
+
+    /// Set the heading in degrees
+    ///
+    /// The heading is marked invalid if it is `None`.
+    pub fn set_heading(&mut self, heading: Option<f64>) {
+        if let Some(heading) = heading {
+            self.heading = (heading.rem_euclid(360.0) * 100.0).round() as u16;
+        }
+        self.set_valid(0x0200, heading.is_some());
+    }
+
+    /// Compute the acceleration
+    ///
+    /// The result is the acceleration in the (x,y,z) direction
+    pub fn acceleration(&self) -> Option<(f64, f64, f64)> {
+        if self.is_acceleration_x_valid()
+            && self.is_acceleration_y_valid()
+            && self.is_acceleration_z_valid()
+        {
+            Some((
+                f64::from(self.acceleration_x) * 20.0 * 1.5 / 32768.0,
+                f64::from(self.acceleration_y) * 20.0 * 1.5 / 32768.0,
+                f64::from(self.acceleration_z) * 20.0 * 1.5 / 32768.0,
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// Compute the gyro rate in degrees/sec
+    ///
+    /// The return is the gyro rate in the (x,y,z) direction    
     pub fn gyro_rate(&self) -> Option<(f64, f64, f64)> {
         if self.is_gyro_rate_x_valid() && self.is_gyro_rate_y_valid() && self.is_gyro_rate_z_valid()
         {
@@ -322,82 +1339,926 @@ impl PitchRollData {
         }
     }
 
-    /// Compute the pitch in degrees
+    /// Compute the pitch in degrees
+    ///
+    /// Bow up is positive
+    pub fn pitch(&self) -> Option<f64> {
+        if self.is_pitch_valid() {
+            Some(f64::from(self.pitch) * 180.0 / 32768.0)
+        } else {
+            None
+        }
+    }
+
+    /// Compute the roll
+    ///
+    /// Port up is positive
+    pub fn roll(&self) -> Option<f64> {
+        if self.is_roll_valid() {
+            Some(f64::from(self.roll) * 180.0 / 32768.0)
+        } else {
+            None
+        }
+    }
+
+    /// Compute the temperature
+    pub fn temperature(&self) -> Option<f64> {
+        if self.is_temperature_valid() {
+            Some(f64::from(self.temperature) * 0.1)
+        } else {
+            None
+        }
+    }
+
+    /// Return the device info
+    pub fn device_info(&self) -> Option<u16> {
+        if self.is_device_info_valid() {
+            Some(self.device_info)
+        } else {
+            None
+        }
+    }
+
+    /// Compute the heading in degrees
+    pub fn heading(&self) -> Option<f64> {
+        if self.is_heading_valid() {
+            Some(f64::from(self.heading) * 0.01)
+        } else {
+            None
+        }
+    }
+
+    /// Compute the heave in millimeters
+    pub fn heave(&self) -> Option<f64> {
+        if self.is_heave_valid() {
+            Some(f64::from(self.heave) / 1000.0)
+        } else {
+            None
+        }
+    }
+
+    /// Compute the yaw in degrees
+    pub fn yaw(&self) -> Option<f64> {
+        if self.is_yaw_valid() {
+            Some(f64::from(self.yaw) * 0.01)
+        } else {
+            None
+        }
+    }
+}
+
+/// The pressure sensor message
+#[binrw]
+#[brw(little,import {message_size:i32})]
+#[derive(Debug, PartialEq)]
+pub struct PressureData {
+    time: i32,
+    #[brw(pad_after = 4)]
+    milliseconds: i32,
+    pressure: i32,
+    temperature: i32,
+    salinity: i32,
+    validity_flag: i32,
+    conductivity: i32,
+    sound_speed: i32,
+    #[brw(pad_after = message_size - 40)]
+    depth: i32,
+}
+
+impl PressureData {
+    /// Return the timestamp
+    pub fn timestamp(&self) -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(i64::from(self.time)).unwrap()
+            + Duration::milliseconds(i64::from(self.milliseconds % 1000))
+    }
+
+    fn is_valid(&self, bit: u32) -> bool {
+        (self.validity_flag >> bit) & 1 == 1
+    }
+
+    /// Return the pressure in PSI
+    pub fn pressure(&self) -> Option<f64> {
+        self.is_valid(0).then(|| f64::from(self.pressure) * 0.001)
+    }
+
+    /// Return the water temperature in degrees Celsius
+    pub fn temperature(&self) -> Option<f64> {
+        self.is_valid(1)
+            .then(|| f64::from(self.temperature) * 0.001)
+    }
+
+    /// Return the salinity in parts per million
+    pub fn salinity(&self) -> Option<f64> {
+        self.is_valid(2).then(|| f64::from(self.salinity))
+    }
+
+    /// Return the conductivity in microsiemens per centimeter
+    pub fn conductivity(&self) -> Option<f64> {
+        self.is_valid(3).then(|| f64::from(self.conductivity))
+    }
+
+    /// Return the speed of sound in m/s
+    pub fn sound_speed(&self) -> Option<f64> {
+        self.is_valid(4)
+            .then(|| f64::from(self.sound_speed) * 0.001)
+    }
+
+    /// Return the depth in meters
+    pub fn depth(&self) -> Option<f64> {
+        self.is_valid(5).then(|| f64::from(self.depth) * 0.001)
+    }
+}
+
+/// The Doppler velocity log message
+#[binrw]
+#[brw(little,import {message_size:i32})]
+#[derive(Debug, PartialEq)]
+pub struct DopplerVelocityLog {
+    time: i32,
+    #[brw(pad_after = 4)]
+    milliseconds: i32,
+    flags: u32,
+    distance_to_bottom: [i32; 4],
+    bottom_velocity: [i16; 3],
+    water_velocity: [i16; 3],
+    depth: u16,
+    pitch: i16,
+    roll: i16,
+    heading: u16,
+    salinity: u16,
+    temperature: i16,
+    #[brw(pad_after = message_size - 58)]
+    sound_speed: i16,
+}
+
+impl DopplerVelocityLog {
+    /// Return the timestamp
+    pub fn timestamp(&self) -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(i64::from(self.time)).unwrap()
+            + Duration::milliseconds(i64::from(self.milliseconds % 1000))
+    }
+
+    fn is_present(&self, bit: u32) -> bool {
+        (self.flags >> bit) & 1 == 1
+    }
+
+    /// Return true if the velocities are in earth coordinates
+    ///
+    /// Otherwise the velocities are in ship coordinates, with x to
+    /// starboard and y forward.
+    pub fn is_earth_coordinates(&self) -> bool {
+        !self.is_present(1)
+    }
+
+    /// Return true if the DVL reported an error
+    pub fn is_error(&self) -> bool {
+        self.is_present(31)
+    }
+
+    /// Return the distance to the bottom in meters along each beam
+    ///
+    /// Beams without a bottom detection are `None`.
+    pub fn distance_to_bottom(&self) -> Option<[Option<f64>; 4]> {
+        self.is_present(5).then(|| {
+            self.distance_to_bottom
+                .map(|d| (d != 0).then(|| f64::from(d) * 0.01))
+        })
+    }
+
+    /// Return the (x, y) velocity relative to the bottom in m/s
+    ///
+    /// In earth coordinates x is east and y is north.
+    pub fn bottom_velocity(&self) -> Option<(f64, f64)> {
+        self.is_present(0).then(|| {
+            (
+                f64::from(self.bottom_velocity[0]) * 0.001,
+                f64::from(self.bottom_velocity[1]) * 0.001,
+            )
+        })
+    }
+
+    /// Return the vertical velocity relative to the bottom in m/s
+    ///
+    /// Up is positive.
+    pub fn bottom_vertical_velocity(&self) -> Option<f64> {
+        self.is_present(2)
+            .then(|| f64::from(self.bottom_velocity[2]) * 0.001)
+    }
+
+    /// Return the (x, y) velocity relative to the water in m/s
+    pub fn water_velocity(&self) -> Option<(f64, f64)> {
+        self.is_present(3).then(|| {
+            (
+                f64::from(self.water_velocity[0]) * 0.001,
+                f64::from(self.water_velocity[1]) * 0.001,
+            )
+        })
+    }
+
+    /// Return the vertical velocity relative to the water in m/s
+    ///
+    /// Up is positive.
+    pub fn water_vertical_velocity(&self) -> Option<f64> {
+        self.is_present(4)
+            .then(|| f64::from(self.water_velocity[2]) * 0.001)
+    }
+
+    /// Return the heading in degrees
+    pub fn heading(&self) -> Option<f64> {
+        self.is_present(6).then(|| f64::from(self.heading) * 0.01)
+    }
+
+    /// Return the pitch in degrees
+    ///
+    /// Bow up is positive
+    pub fn pitch(&self) -> Option<f64> {
+        self.is_present(7).then(|| f64::from(self.pitch) * 0.01)
+    }
+
+    /// Return the roll in degrees
+    ///
+    /// Port up is positive
+    pub fn roll(&self) -> Option<f64> {
+        self.is_present(8).then(|| f64::from(self.roll) * 0.01)
+    }
+
+    /// Return the water temperature in degrees Celsius
+    pub fn temperature(&self) -> Option<f64> {
+        self.is_present(9)
+            .then(|| f64::from(self.temperature) * 0.01)
+    }
+
+    /// Return the depth in meters
+    pub fn depth(&self) -> Option<f64> {
+        self.is_present(10).then(|| f64::from(self.depth) * 0.1)
+    }
+
+    /// Return the salinity in parts per thousand
+    pub fn salinity(&self) -> Option<f64> {
+        self.is_present(11).then(|| f64::from(self.salinity))
+    }
+
+    /// Return the speed of sound in m/s
+    pub fn sound_speed(&self) -> Option<f64> {
+        self.is_present(12).then(|| f64::from(self.sound_speed))
+    }
+
+    /// Return the speed over ground in m/s and the track in degrees
+    ///
+    /// This is only available for bottom velocities in earth coordinates.
+    fn course(&self) -> Option<(f64, f64)> {
+        if !self.is_earth_coordinates() {
+            return None;
+        }
+        let (east, north) = self.bottom_velocity()?;
+        let track = east.atan2(north).to_degrees().rem_euclid(360.0);
+        Some((east.hypot(north), track))
+    }
+}
+
+/// The situation message
+#[binrw]
+#[brw(little,import {message_size:i32})]
+#[derive(Debug, PartialEq)]
+pub struct SituationData {
+    time: i32,
+    #[brw(pad_after = 4)]
+    milliseconds: i32,
+    #[brw(pad_after = 4)]
+    validity_flag: u32,
+    microsecond_timestamp: u64,
+    latitude: f64,
+    longitude: f64,
+    depth: f64,
+    heading: f64,
+    pitch: f64,
+    roll: f64,
+    relative_position: [f64; 3],
+    velocity: [f64; 3],
+    ned_velocity: [f64; 3],
+    angular_rate: [f64; 3],
+    acceleration: [f64; 3],
+    #[brw(pad_after = message_size - 244)]
+    standard_deviation: [f64; 6],
+}
+
+impl SituationData {
+    fn is_valid(&self, bit: u32) -> bool {
+        (self.validity_flag >> bit) & 1 == 1
+    }
+
+    /// Return the timestamp
+    ///
+    /// This is the microsecond timestamp if it is valid.
+    pub fn timestamp(&self) -> OffsetDateTime {
+        if self.is_valid(0) {
+            if let Ok(timestamp) = OffsetDateTime::from_unix_timestamp_nanos(
+                i128::from(self.microsecond_timestamp) * 1000,
+            ) {
+                return timestamp;
+            }
+        }
+        OffsetDateTime::from_unix_timestamp(i64::from(self.time)).unwrap()
+            + Duration::milliseconds(i64::from(self.milliseconds % 1000))
+    }
+
+    /// Return the latitude in degrees
+    pub fn latitude(&self) -> Option<f64> {
+        self.is_valid(1).then_some(self.latitude)
+    }
+
+    /// Return the longitude in degrees
+    pub fn longitude(&self) -> Option<f64> {
+        self.is_valid(2).then_some(self.longitude)
+    }
+
+    /// Return the depth in meters
+    pub fn depth(&self) -> Option<f64> {
+        self.is_valid(3).then_some(self.depth)
+    }
+
+    /// Return the heading in degrees
+    pub fn heading(&self) -> Option<f64> {
+        self.is_valid(4).then_some(self.heading)
+    }
+
+    /// Return the pitch in degrees
+    ///
+    /// Bow up is positive
+    pub fn pitch(&self) -> Option<f64> {
+        self.is_valid(5).then_some(self.pitch)
+    }
+
+    /// Return the roll in degrees
+    ///
+    /// Port up is positive
+    pub fn roll(&self) -> Option<f64> {
+        self.is_valid(6).then_some(self.roll)
+    }
+
+    /// Return the (x, y, z) position relative to the reference point in meters
+    pub fn relative_position(&self) -> Option<(f64, f64, f64)> {
+        let [x, y, z] = self.relative_position;
+        self.is_valid(7).then_some((x, y, z))
+    }
+
+    /// Return the (x, y, z) velocity in m/s
+    pub fn velocity(&self) -> Option<(f64, f64, f64)> {
+        let [x, y, z] = self.velocity;
+        self.is_valid(8).then_some((x, y, z))
+    }
+
+    /// Return the (north, east, down) velocity in m/s
+    pub fn ned_velocity(&self) -> Option<(f64, f64, f64)> {
+        let [n, e, d] = self.ned_velocity;
+        self.is_valid(9).then_some((n, e, d))
+    }
+
+    /// Return the (x, y, z) angular rate in degrees/sec
+    pub fn angular_rate(&self) -> Option<(f64, f64, f64)> {
+        let [x, y, z] = self.angular_rate;
+        self.is_valid(10).then_some((x, y, z))
+    }
+
+    /// Return the (x, y, z) acceleration in m/s^2
+    pub fn acceleration(&self) -> Option<(f64, f64, f64)> {
+        let [x, y, z] = self.acceleration;
+        self.is_valid(11).then_some((x, y, z))
+    }
+}
+
+/// The comprehensive situation message
+#[binrw]
+#[brw(little,import {message_size:i32})]
+#[derive(Debug, PartialEq)]
+pub struct SituationComprehensive {
+    time: i32,
+    #[brw(pad_after = 4)]
+    milliseconds: i32,
+    #[brw(pad_after = 4)]
+    validity_flag: u32,
+    microsecond_timestamp: u64,
+    latitude: f64,
+    longitude: f64,
+    depth: f64,
+    altitude: f64,
+    heave: f64,
+    velocity: [f64; 2],
+    velocity_down: f64,
+    pitch: f64,
+    roll: f64,
+    heading: f64,
+    sound_speed: f64,
+    #[brw(pad_after = message_size - 132)]
+    temperature: f64,
+}
+
+impl SituationComprehensive {
+    fn is_valid(&self, bit: u32) -> bool {
+        (self.validity_flag >> bit) & 1 == 1
+    }
+
+    /// Return the timestamp
+    ///
+    /// This is the microsecond timestamp if it is valid.
+    pub fn timestamp(&self) -> OffsetDateTime {
+        if self.is_valid(0) {
+            if let Ok(timestamp) = OffsetDateTime::from_unix_timestamp_nanos(
+                i128::from(self.microsecond_timestamp) * 1000,
+            ) {
+                return timestamp;
+            }
+        }
+        OffsetDateTime::from_unix_timestamp(i64::from(self.time)).unwrap()
+            + Duration::milliseconds(i64::from(self.milliseconds % 1000))
+    }
+
+    /// Return the latitude in degrees
+    pub fn latitude(&self) -> Option<f64> {
+        self.is_valid(1).then_some(self.latitude)
+    }
+
+    /// Return the longitude in degrees
+    pub fn longitude(&self) -> Option<f64> {
+        self.is_valid(2).then_some(self.longitude)
+    }
+
+    /// Return the depth in meters
+    pub fn depth(&self) -> Option<f64> {
+        self.is_valid(3).then_some(self.depth)
+    }
+
+    /// Return the altitude above the seafloor in meters
+    pub fn altitude(&self) -> Option<f64> {
+        self.is_valid(4).then_some(self.altitude)
+    }
+
+    /// Return the heave in meters
+    pub fn heave(&self) -> Option<f64> {
+        self.is_valid(5).then_some(self.heave)
+    }
+
+    /// Return the horizontal velocity components in m/s
+    ///
+    /// These are north and east, or forward and starboard, depending on
+    /// the navigation system.
+    pub fn velocity(&self) -> Option<(f64, f64)> {
+        let [v1, v2] = self.velocity;
+        self.is_valid(6).then_some((v1, v2))
+    }
+
+    /// Return the downward velocity in m/s
+    pub fn velocity_down(&self) -> Option<f64> {
+        self.is_valid(7).then_some(self.velocity_down)
+    }
+
+    /// Return the pitch in degrees
+    ///
+    /// Bow up is positive
+    pub fn pitch(&self) -> Option<f64> {
+        self.is_valid(8).then_some(self.pitch)
+    }
+
+    /// Return the roll in degrees
+    ///
+    /// Port up is positive
+    pub fn roll(&self) -> Option<f64> {
+        self.is_valid(9).then_some(self.roll)
+    }
+
+    /// Return the heading in degrees
+    pub fn heading(&self) -> Option<f64> {
+        self.is_valid(10).then_some(self.heading)
+    }
+
+    /// Return the speed of sound in m/s
+    pub fn sound_speed(&self) -> Option<f64> {
+        self.is_valid(11).then_some(self.sound_speed)
+    }
+
+    /// Return the water temperature in degrees Celsius
+    pub fn temperature(&self) -> Option<f64> {
+        self.is_valid(12).then_some(self.temperature)
+    }
+}
+
+/// The cable counter message
+#[binrw]
+#[brw(little,import {message_size:i32})]
+#[derive(Debug, PartialEq)]
+pub struct CableCounter {
+    time: i32,
+    #[brw(pad_after = 4)]
+    milliseconds: i32,
+    cable_length: f32,
+    cable_speed: f32,
+    cable_length_valid: i16,
+    cable_speed_valid: i16,
+    cable_counter_error: i16,
+    cable_tension_valid: i16,
+    #[brw(pad_after = message_size - 32)]
+    cable_tension: f32,
+}
+
+impl CableCounter {
+    /// Return the timestamp
+    pub fn timestamp(&self) -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(i64::from(self.time)).unwrap()
+            + Duration::milliseconds(i64::from(self.milliseconds % 1000))
+    }
+
+    /// Return the length of cable out in meters
+    pub fn cable_length(&self) -> Option<f64> {
+        (self.cable_length_valid != 0).then_some(f64::from(self.cable_length))
+    }
+
+    /// Return the speed of the cable in m/s
+    pub fn cable_speed(&self) -> Option<f64> {
+        (self.cable_speed_valid != 0).then_some(f64::from(self.cable_speed))
+    }
+
+    /// Return the cable tension in kilograms
+    pub fn cable_tension(&self) -> Option<f64> {
+        (self.cable_tension_valid != 0).then_some(f64::from(self.cable_tension))
+    }
+
+    /// Return true if the cable counter reported an error
+    pub fn is_error(&self) -> bool {
+        self.cable_counter_error != 0
+    }
+}
+
+/// The kilometer of pipe message
+#[binrw]
+#[brw(little,import {message_size:i32})]
+#[derive(Debug, PartialEq)]
+pub struct KilometerOfPipe {
+    time: i32,
+    #[brw(pad_after = 4)]
+    milliseconds: i32,
+    kilometer_of_pipe: f32,
+    #[brw(pad_after = message_size - 18)]
+    flag: i16,
+}
+
+impl KilometerOfPipe {
+    /// Return the timestamp
+    pub fn timestamp(&self) -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(i64::from(self.time)).unwrap()
+            + Duration::milliseconds(i64::from(self.milliseconds % 1000))
+    }
+
+    /// Return the kilometer of pipe
+    pub fn kilometer_of_pipe(&self) -> Option<f64> {
+        (self.flag != 0).then_some(f64::from(self.kilometer_of_pipe))
+    }
+}
+
+/// A sample of a bathymetric data message
+#[binrw]
+#[brw(little)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BathymetricSample {
+    time_delay: u16,
+    angle: i16,
+    amplitude: u8,
+    angle_uncertainty: u8,
+    flags: u8,
+    quality: u8,
+}
+
+impl BathymetricSample {
+    /// Return the amplitude of the return
+    pub fn amplitude(&self) -> u8 {
+        self.amplitude
+    }
+
+    /// Return the flags of the sample
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    /// Return the signal to noise ratio or quality of the sample
+    pub fn quality(&self) -> u8 {
+        self.quality
+    }
+}
+
+/// The bathymetric data message
+///
+/// The samples start at the offset to the first sample, counted in bytes
+/// from the start of the message body.
+#[binrw]
+#[brw(little,import {message_size:i32})]
+#[derive(Debug, PartialEq)]
+pub struct BathymetricData {
+    time: i32,
+    nanoseconds: u32,
+    ping_number: u32,
+    n_samples: u16,
+    channel: u8,
+    algorithm_type: u8,
+    n_pulses: u8,
+    pulse_phase: u8,
+    pulse_length: u16,
+    transmit_amplitude: f32,
+    start_frequency: f32,
+    end_frequency: f32,
+    mixer_frequency: f32,
+    sample_rate: f32,
+    #[br(assert(
+        offset_to_first_sample >= 80
+            && i64::from(offset_to_first_sample) + 8 * i64::from(n_samples)
+                <= i64::from(message_size),
+        "{} samples at offset {} do not fit in a message of size {}",
+        n_samples,
+        offset_to_first_sample,
+        message_size
+    ))]
+    #[bw(assert(
+        *offset_to_first_sample >= 80
+            && i64::from(*offset_to_first_sample) + 8 * i64::from(*n_samples)
+                <= i64::from(message_size),
+        "{} samples at offset {} do not fit in a message of size {}",
+        n_samples,
+        offset_to_first_sample,
+        message_size
+    ))]
+    offset_to_first_sample: u32,
+    time_delay_uncertainty: f32,
+    time_scale_factor: f32,
+    time_scale_accuracy: f32,
+    #[brw(pad_after = 4)]
+    angle_scale_factor: f32,
+    time_to_first_bottom: f32,
+    format_revision: u8,
+    binning_flag: u8,
+    #[brw(pad_after = 1)]
+    tvg: u8,
+    #[brw(pad_after = 4)]
+    span: f32,
+    #[br(count = n_samples)]
+    #[br(pad_before = offset_to_first_sample - 80)]
+    #[bw(pad_before = *offset_to_first_sample - 80)]
+    #[br(pad_after = message_size - offset_to_first_sample as i32 - 8 * i32::from(n_samples))]
+    #[bw(pad_after = message_size - *offset_to_first_sample as i32 - 8 * i32::from(*n_samples))]
+    samples: Vec<BathymetricSample>,
+}
+
+impl BathymetricData {
+    /// Return the timestamp
+    pub fn timestamp(&self) -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(i64::from(self.time)).unwrap()
+            + Duration::nanoseconds(i64::from(self.nanoseconds % 1_000_000_000))
+    }
+
+    /// Return the ping number
+    pub fn ping_number(&self) -> u32 {
+        self.ping_number
+    }
+
+    /// Return the mixer frequency in hertz
+    pub fn mixer_frequency(&self) -> f64 {
+        f64::from(self.mixer_frequency)
+    }
+
+    /// Return the sample rate in hertz
+    pub fn sample_rate(&self) -> f64 {
+        f64::from(self.sample_rate)
+    }
+
+    /// Return the angular span of the swath in degrees
+    pub fn span(&self) -> f64 {
+        f64::from(self.span)
+    }
+
+    /// Return the samples as they are stored
+    pub fn samples(&self) -> &[BathymetricSample] {
+        &self.samples
+    }
+
+    /// Return the two way travel time of a sample in seconds
+    pub fn travel_time(&self, sample: &BathymetricSample) -> f64 {
+        f64::from(sample.time_delay) * f64::from(self.time_scale_factor)
+    }
+
+    /// Return the angle of arrival of a sample in degrees
+    pub fn angle(&self, sample: &BathymetricSample) -> f64 {
+        f64::from(sample.angle) * f64::from(self.angle_scale_factor)
+    }
+
+    /// Return the uncertainty of the angle of arrival of a sample in degrees
+    pub fn angle_uncertainty(&self, sample: &BathymetricSample) -> f64 {
+        f64::from(sample.angle_uncertainty) * f64::from(self.angle_scale_factor)
+    }
+
+    /// Return the samples as soundings with a travel time and angle
+    pub fn soundings(&self) -> Vec<Sounding> {
+        self.samples
+            .iter()
+            .map(|sample| Sounding {
+                travel_time: Some(self.travel_time(sample)),
+                angle: Some(self.angle(sample)),
+                depth: None,
+                across_track: None,
+                along_track: None,
+                amplitude: Some(f64::from(sample.amplitude)),
+            })
+            .collect()
+    }
+}
+
+/// The attitude message of a bathymetric system
+#[binrw]
+#[brw(little,import {message_size:i32})]
+#[derive(Debug, PartialEq)]
+pub struct AttitudeData {
+    time: i32,
+    nanoseconds: u32,
+    validity_flag: u32,
+    heading: f32,
+    heave: f32,
+    pitch: f32,
+    roll: f32,
+    #[brw(pad_after = message_size - 32)]
+    yaw: f32,
+}
+
+impl AttitudeData {
+    /// Return the timestamp
+    pub fn timestamp(&self) -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(i64::from(self.time)).unwrap()
+            + Duration::nanoseconds(i64::from(self.nanoseconds % 1_000_000_000))
+    }
+
+    fn is_valid(&self, bit: u32) -> bool {
+        (self.validity_flag >> bit) & 1 == 1
+    }
+
+    /// Return the heading in degrees
+    pub fn heading(&self) -> Option<f64> {
+        self.is_valid(0).then_some(f64::from(self.heading))
+    }
+
+    /// Return the heave in meters
+    ///
+    /// Down is positive
+    pub fn heave(&self) -> Option<f64> {
+        self.is_valid(1).then_some(f64::from(self.heave))
+    }
+
+    /// Return the pitch in degrees
     ///
     /// Bow up is positive
     pub fn pitch(&self) -> Option<f64> {
-        if self.is_pitch_valid() {
-            Some(f64::from(self.pitch) * 180.0 / 32768.0)
-        } else {
-            None
-        }
+        self.is_valid(2).then_some(f64::from(self.pitch))
     }
 
-    /// Compute the roll
+    /// Return the roll in degrees
     ///
     /// Port up is positive
     pub fn roll(&self) -> Option<f64> {
-        if self.is_roll_valid() {
-            Some(f64::from(self.roll) * 180.0 / 32768.0)
-        } else {
-            None
-        }
+        self.is_valid(3).then_some(f64::from(self.roll))
     }
 
-    /// Compute the temperature
+    /// Return the yaw in degrees
+    pub fn yaw(&self) -> Option<f64> {
+        self.is_valid(4).then_some(f64::from(self.yaw))
+    }
+}
+
+/// The pressure and sound speed message of a bathymetric system
+#[binrw]
+#[brw(little,import {message_size:i32})]
+#[derive(Debug, PartialEq)]
+pub struct PressureSoundSpeed {
+    time: i32,
+    nanoseconds: u32,
+    validity_flag: u32,
+    pressure: f32,
+    temperature: f32,
+    salinity: f32,
+    conductivity: f32,
+    sound_speed: f32,
+    #[brw(pad_after = message_size - 36)]
+    depth: f32,
+}
+
+impl PressureSoundSpeed {
+    /// Return the timestamp
+    pub fn timestamp(&self) -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(i64::from(self.time)).unwrap()
+            + Duration::nanoseconds(i64::from(self.nanoseconds % 1_000_000_000))
+    }
+
+    fn is_valid(&self, bit: u32) -> bool {
+        (self.validity_flag >> bit) & 1 == 1
+    }
+
+    /// Return the absolute pressure in PSI
+    pub fn pressure(&self) -> Option<f64> {
+        self.is_valid(0).then_some(f64::from(self.pressure))
+    }
+
+    /// Return the water temperature in degrees Celsius
     pub fn temperature(&self) -> Option<f64> {
-        if self.is_temperature_valid() {
-            Some(f64::from(self.temperature) * 0.1)
-        } else {
-            None
-        }
+        self.is_valid(1).then_some(f64::from(self.temperature))
     }
 
-    /// Return the device info
-    pub fn device_info(&self) -> Option<u16> {
-        if self.is_device_info_valid() {
-            Some(self.device_info)
-        } else {
-            None
-        }
+    /// Return the salinity in parts per thousand
+    pub fn salinity(&self) -> Option<f64> {
+        self.is_valid(2).then_some(f64::from(self.salinity))
     }
 
-    /// Compute the heading in degrees
-    pub fn heading(&self) -> Option<f64> {
-        if self.is_heading_valid() {
-            Some(f64::from(self.heading) * 0.01)
-        } else {
-            None
-        }
+    /// Return the conductivity in microsiemens per centimeter
+    pub fn conductivity(&self) -> Option<f64> {
+        self.is_valid(3).then_some(f64::from(self.conductivity))
     }
 
-    /// Compute the heave in millimeters
-    pub fn heave(&self) -> Option<f64> {
-        if self.is_heave_valid() {
-            Some(f64::from(self.heave) / 1000.0)
-        } else {
-            None
-        }
+    /// Return the speed of sound in m/s
+    pub fn sound_speed(&self) -> Option<f64> {
+        self.is_valid(4).then_some(f64::from(self.sound_speed))
     }
 
-    /// Compute the yaw in degrees
-    pub fn yaw(&self) -> Option<f64> {
-        if self.is_yaw_valid() {
-            Some(f64::from(self.yaw) * 0.01)
-        } else {
-            None
-        }
+    /// Return the depth in meters
+    pub fn depth(&self) -> Option<f64> {
+        self.is_valid(5).then_some(f64::from(self.depth))
+    }
+}
+
+/// The position message of a bathymetric system
+#[binrw]
+#[brw(little,import {message_size:i32})]
+#[derive(Debug, PartialEq)]
+pub struct PositionData {
+    time: i32,
+    nanoseconds: u32,
+    validity_flag: u32,
+    latitude: f64,
+    longitude: f64,
+    #[brw(pad_after = message_size - 32)]
+    height: f32,
+}
+
+impl PositionData {
+    /// Return the timestamp
+    pub fn timestamp(&self) -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(i64::from(self.time)).unwrap()
+            + Duration::nanoseconds(i64::from(self.nanoseconds % 1_000_000_000))
+    }
+
+    fn is_valid(&self, bit: u32) -> bool {
+        (self.validity_flag >> bit) & 1 == 1
+    }
+
+    /// Return the latitude in degrees
+    pub fn latitude(&self) -> Option<f64> {
+        self.is_valid(0).then_some(self.latitude)
+    }
+
+    /// Return the longitude in degrees
+    pub fn longitude(&self) -> Option<f64> {
+        self.is_valid(0).then_some(self.longitude)
+    }
+
+    /// Return the ellipsoidal height in meters
+    pub fn height(&self) -> Option<f64> {
+        self.is_valid(1).then_some(f64::from(self.height))
+    }
+}
+
+/// Return the size in bytes of the fixed part of a parsed message body
+///
+/// This is `None` for message types that are not parsed by SDW.
+fn body_size(message_type: u16) -> Option<i32> {
+    match message_type {
+        80 => Some(240),
+        181 => Some(64),
+        182 => Some(24),
+        2002 => Some(12),
+        2020 => Some(44),
+        2060 => Some(40),
+        2080 => Some(58),
+        2090 => Some(244),
+        2091 => Some(132),
+        2100 => Some(32),
+        2101 => Some(18),
+        3000 => Some(80),
+        3001 => Some(32),
+        3002 => Some(36),
+        3004 => Some(32),
+        _ => None,
     }
 }
 
-#[binread]
-#[br(import {message_type:u16,
+#[binrw]
+#[brw(import {message_type:u16,
 	     message_size:i32})]
 #[derive(Debug, PartialEq)]
 enum MessageType {
     #[br(pre_assert(message_type==80))]
     M80 {
-        #[br(args {message_size})]
+        #[brw(args {message_size})]
         msg: SonarData,
     },
     #[br(pre_assert(message_type==2020))]
@@ -406,24 +2267,126 @@ enum MessageType {
     M181 { msg: NavigationOffsets },
     #[br(pre_assert(message_type==182))]
     M182 {
-        #[br(args {message_size})]
+        #[brw(args {message_size})]
         msg: SystemInformation,
     },
     #[br(pre_assert(message_type==2002))]
     M2002 {
-        #[br(args {message_size})]
+        #[brw(args {message_size})]
         msg: NMEAString,
     },
+    #[br(pre_assert(message_type==2060))]
+    M2060 {
+        #[brw(args {message_size})]
+        msg: PressureData,
+    },
+    #[br(pre_assert(message_type==2080))]
+    M2080 {
+        #[brw(args {message_size})]
+        msg: DopplerVelocityLog,
+    },
+    #[br(pre_assert(message_type==2090))]
+    M2090 {
+        #[brw(args {message_size})]
+        msg: SituationData,
+    },
+    #[br(pre_assert(message_type==2091))]
+    M2091 {
+        #[brw(args {message_size})]
+        msg: SituationComprehensive,
+    },
+    #[br(pre_assert(message_type==2100))]
+    M2100 {
+        #[brw(args {message_size})]
+        msg: CableCounter,
+    },
+    #[br(pre_assert(message_type==2101))]
+    M2101 {
+        #[brw(args {message_size})]
+        msg: KilometerOfPipe,
+    },
+    #[br(pre_assert(message_type==3000))]
+    M3000 {
+        #[brw(args {message_size})]
+        msg: BathymetricData,
+    },
+    #[br(pre_assert(message_type==3001))]
+    M3001 {
+        #[brw(args {message_size})]
+        msg: AttitudeData,
+    },
+    #[br(pre_assert(message_type==3002))]
+    M3002 {
+        #[brw(args {message_size})]
+        msg: PressureSoundSpeed,
+    },
+    #[br(pre_assert(message_type==3004))]
+    M3004 {
+        #[brw(args {message_size})]
+        msg: PositionData,
+    },
+    #[br(pre_assert(body_size(message_type).is_none()))]
     M0 {
-        #[br(args {message_size})]
+        #[brw(args {message_size})]
         msg: UnknownMessage,
     },
 }
 
+/// A jump in the clock between consecutive pings
+///
+/// Pings are compared with the previous ping of the same subsystem and
+/// channel. The clock jumps if it runs backwards or skips forward by more
+/// than the maximum gap of the [`File`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Discontinuity {
+    /// The byte offset of the message after the jump
+    pub offset: u64,
+    /// The subsystem number of the pings
+    pub subsystem: u8,
+    /// The channel number of the pings
+    pub channel: u8,
+    /// The timestamp of the ping before the jump
+    pub previous: OffsetDateTime,
+    /// The timestamp of the ping after the jump
+    pub timestamp: OffsetDateTime,
+}
+
+/// A ping whose timestamp fails the cross-check of its time sources
+///
+/// See [`SonarData::timestamp`]. The records of the ping have the time
+/// since the epoch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidTimestamp {
+    /// The byte offset of the message
+    pub offset: u64,
+    /// The subsystem number of the ping
+    pub subsystem: u8,
+    /// The channel number of the ping
+    pub channel: u8,
+    /// The error found by the cross-check
+    pub error: TimestampError,
+}
+
 /// An Iterator interface to a JSF file
 pub struct File<T: io::Read + io::Seek> {
     /// The reader from which bytes are read and parsed
     reader: T,
+    /// The largest forward step of the clock that is not a discontinuity
+    max_gap: Duration,
+    /// The timestamp of the last ping of each subsystem and channel
+    last: HashMap<(u8, u8), OffsetDateTime>,
+    /// The clock discontinuities found so far
+    discontinuities: Vec<Discontinuity>,
+    /// The pings with an invalid timestamp found so far
+    invalid_timestamps: Vec<InvalidTimestamp>,
+    /// Whether to scan past messages that cannot be read
+    recover: bool,
+    /// The byte ranges skipped while recovering
+    skipped: Vec<Range<u64>>,
+    /// The protocol version of the first message
+    protocol: Option<u8>,
+    /// The message types read so far
+    message_types: HashSet<u16>,
 }
 
 impl<T> File<T>
@@ -432,7 +2395,236 @@ where
 {
     /// Create a JSF file from a reader
     pub fn new(reader: T) -> Self {
-        File { reader }
+        File {
+            reader,
+            max_gap: Duration::minutes(1),
+            last: HashMap::new(),
+            discontinuities: Vec::new(),
+            invalid_timestamps: Vec::new(),
+            recover: false,
+            skipped: Vec::new(),
+            protocol: None,
+            message_types: HashSet::new(),
+        }
+    }
+
+    /// Set whether to recover from messages that cannot be read
+    ///
+    /// By default, the iterator returns the error of a message that cannot
+    /// be read, and the next message is read from wherever the error left
+    /// the reader. In recovery mode, the iterator instead scans forward
+    /// from the start of the bad message to the next message that can be
+    /// read, and returns that. A candidate message must start with a
+    /// plausible header and be read without error. A header is plausible
+    /// if it has the JSF marker, the protocol version of the first message
+    /// of the file, a message type that SDW parses or has already read
+    /// from the file, and a message size that fits in the file. Since the
+    /// body of a message that SDW does not parse is not checked, such a
+    /// message must also be followed by either another header or the end
+    /// of the file. Any other message, whether read in turn or found while
+    /// scanning, is bad if another header occurs before its end, which
+    /// catches a corrupted message size that swallows the following
+    /// messages. The bytes that are passed over are listed by
+    /// [`File::skipped`]. A message that is cut off by the end of the file
+    /// is skipped in the same way.
+    pub fn set_recover(&mut self, recover: bool) {
+        self.recover = recover;
+    }
+
+    /// Return the byte ranges skipped while recovering from bad messages
+    pub fn skipped(&self) -> &[Range<u64>] {
+        &self.skipped
+    }
+
+    /// Set the largest forward step of the clock that is not a discontinuity
+    ///
+    /// The default is one minute.
+    pub fn set_max_gap(&mut self, max_gap: Duration) {
+        self.max_gap = max_gap;
+    }
+
+    /// Return the clock discontinuities between the pings read so far
+    ///
+    /// Pings are compared by their time since the epoch. Pings for which
+    /// this cannot be represented are ignored.
+    pub fn discontinuities(&self) -> &[Discontinuity] {
+        &self.discontinuities
+    }
+
+    /// Return the pings read so far whose timestamp fails the cross-check
+    pub fn invalid_timestamps(&self) -> &[InvalidTimestamp] {
+        &self.invalid_timestamps
+    }
+
+    /// Record a message that is returned by the iterator
+    fn accept(&mut self, offset: u64, msg: &Message) {
+        self.protocol.get_or_insert(msg.protocol);
+        self.message_types.insert(msg.message_type);
+        self.check_clock(offset, msg);
+    }
+
+    /// Cross-check the timestamp of a ping and compare it with the
+    /// previous ping
+    fn check_clock(&mut self, offset: u64, msg: &Message) {
+        let mt = match &msg.data {
+            MessageType::M80 { msg: mt } => mt,
+            _ => return,
+        };
+        if let Err(error) = mt.timestamp() {
+            self.invalid_timestamps.push(InvalidTimestamp {
+                offset,
+                subsystem: msg.subsystem_number,
+                channel: msg.channel_number,
+                error,
+            });
+        }
+        let timestamp = match mt.epoch_timestamp() {
+            Ok(timestamp) => timestamp,
+            Err(_) => return,
+        };
+        let key = (msg.subsystem_number, msg.channel_number);
+        if let Some(previous) = self.last.insert(key, timestamp) {
+            if timestamp < previous || timestamp - previous > self.max_gap {
+                self.discontinuities.push(Discontinuity {
+                    offset,
+                    subsystem: msg.subsystem_number,
+                    channel: msg.channel_number,
+                    previous,
+                    timestamp,
+                });
+            }
+        }
+    }
+
+    /// Return true if the reader is at the end of the file or a header
+    ///
+    /// The header may belong to a message that is cut off by the end of
+    /// the file. The position of the reader is not changed.
+    fn at_boundary(&mut self) -> io::Result<bool> {
+        let start = self.reader.stream_position()?;
+        let end = self.reader.seek(SeekFrom::End(0))?;
+        let boundary = start == end || self.message_len(start)?.is_some();
+        self.reader.seek(SeekFrom::Start(start))?;
+        Ok(boundary)
+    }
+
+    /// Return the length of the message if a plausible header starts at `offset`
+    ///
+    /// The header must start with the JSF marker and have the protocol
+    /// version of the file, a message type that is parsed by SDW or was
+    /// read before, and a non-negative message size that is large enough
+    /// for the type. The position of the reader is left anywhere.
+    fn message_len(&mut self, offset: u64) -> io::Result<Option<u64>> {
+        self.reader.seek(SeekFrom::Start(offset))?;
+        let mut header = Vec::with_capacity(16);
+        self.reader.by_ref().take(16).read_to_end(&mut header)?;
+        if header.len() < 16 || header[..2] != *b"\x01\x16" {
+            return Ok(None);
+        }
+        if matches!(self.protocol, Some(protocol) if protocol != header[2]) {
+            return Ok(None);
+        }
+        let message_type = u16::from_le_bytes([header[4], header[5]]);
+        let size = i32::from_le_bytes([header[12], header[13], header[14], header[15]]);
+        let known = match body_size(message_type) {
+            Some(body_size) => size >= body_size,
+            None => size >= 0 && self.message_types.contains(&message_type),
+        };
+        Ok(known.then_some(16 + size as u64))
+    }
+
+    /// Return the offset of the next message header at or after `start`
+    ///
+    /// Only markers that start a plausible header of a message that fits
+    /// in the file are considered.
+    fn find_marker(&mut self, start: u64) -> io::Result<Option<u64>> {
+        let end = self.reader.seek(SeekFrom::End(0))?;
+        let mut candidate = start;
+        while let Some(offset) = self.find_magic(candidate)? {
+            if matches!(self.message_len(offset)?, Some(len) if offset + len <= end) {
+                return Ok(Some(offset));
+            }
+            candidate = offset + 1;
+        }
+        Ok(None)
+    }
+
+    /// Return the offset of the next JSF marker at or after `start`
+    fn find_magic(&mut self, start: u64) -> io::Result<Option<u64>> {
+        self.reader.seek(SeekFrom::Start(start))?;
+        let mut buf = [0u8; 4096];
+        let mut position = start;
+        let mut previous = None;
+        loop {
+            let n = self.reader.read(&mut buf)?;
+            if n == 0 {
+                return Ok(None);
+            }
+            for (i, &byte) in buf[..n].iter().enumerate() {
+                if previous == Some(0x01) && byte == 0x16 {
+                    return Ok(Some(position + i as u64 - 1));
+                }
+                previous = Some(byte);
+            }
+            position += n as u64;
+        }
+    }
+
+    /// Return true if the message just read from `start` ends where it should
+    ///
+    /// A message is complete if it is followed by the end of the file or
+    /// the marker of another message. Otherwise it is only complete if no
+    /// marker occurs before its end, since a message whose size is too
+    /// large swallows the start of the next message. A good message that
+    /// is followed by garbage is therefore still complete. The position of
+    /// the reader is not changed.
+    fn is_complete(&mut self, start: u64) -> io::Result<bool> {
+        if self.at_boundary()? {
+            return Ok(true);
+        }
+        let end = self.reader.stream_position()?;
+        let next = self.find_marker(start + 1)?;
+        self.reader.seek(SeekFrom::Start(end))?;
+        Ok(!matches!(next, Some(next) if next < end))
+    }
+
+    /// Skip a bad message at `start` and return the next good message
+    fn recover_from(&mut self, start: u64) -> Option<BinResult<Message>> {
+        match self.resync(start) {
+            Ok(Some((offset, msg))) => {
+                self.accept(offset, &msg);
+                Some(Ok(msg))
+            }
+            Ok(None) => None,
+            Err(e) => Some(Err(e.into())),
+        }
+    }
+
+    /// Skip forward from a bad message at `start` to the next good message
+    ///
+    /// Returns the offset of the message and the message, or `None` if
+    /// there are no more messages. The skipped bytes are recorded.
+    fn resync(&mut self, start: u64) -> io::Result<Option<(u64, Message)>> {
+        let mut candidate = start;
+        while let Some(offset) = self.find_marker(candidate + 1)? {
+            self.reader.seek(SeekFrom::Start(offset))?;
+            if let Ok(msg) = Message::read(&mut self.reader) {
+                let good = if msg.is_unknown() {
+                    self.at_boundary()?
+                } else {
+                    self.is_complete(offset)?
+                };
+                if good {
+                    self.skipped.push(start..offset);
+                    return Ok(Some((offset, msg)));
+                }
+            }
+            candidate = offset;
+        }
+
+        let end = self.reader.seek(SeekFrom::End(0))?;
+        self.skipped.push(start..end);
+        Ok(None)
     }
 }
 
@@ -476,9 +2668,32 @@ impl<T: io::Read + io::Seek> Iterator for File<T> {
     type Item = BinResult<Message>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        let offset = match self.reader.stream_position() {
+            Ok(offset) => offset,
+            Err(e) => return Some(Err(e.into())),
+        };
         let res = Message::read(&mut self.reader);
         match res {
-            Ok(msg) => Some(Ok(msg)),
+            Ok(msg) if !self.recover => {
+                self.accept(offset, &msg);
+                Some(Ok(msg))
+            }
+            Ok(msg) => match self.is_complete(offset) {
+                Ok(true) => {
+                    self.accept(offset, &msg);
+                    Some(Ok(msg))
+                }
+                Ok(false) => self.recover_from(offset),
+                Err(e) => Some(Err(e.into())),
+            },
+            Err(_) if self.recover => {
+                // A clean end of file is not a bad message
+                match self.reader.seek(SeekFrom::End(0)) {
+                    Ok(end) if end == offset => None,
+                    Ok(_) => self.recover_from(offset),
+                    Err(e) => Some(Err(e.into())),
+                }
+            }
             Err(e) => {
                 if e.is_eof() {
                     None
@@ -490,19 +2705,168 @@ impl<T: io::Read + io::Seek> Iterator for File<T> {
     }
 }
 
+/// A writer of JSF files
+///
+/// Messages are written in the order that they are given, so that a file
+/// can be cut into pieces or filtered by reading it with a [`File`] and
+/// writing the messages to keep. Navigation can be corrected on the way
+/// through with the setters of [`SonarData`] and [`PitchRollData`], and
+/// [`Message::set_nmea_sentence`].
+///
+/// Messages that are not parsed are written back byte for byte. Parsed
+/// messages are not: their reserved fields, and any bytes that SDW does
+/// not read, are written as zeros. A file only round-trips exactly if
+/// these are zero in the original.
+///
+/// ```
+/// # use sdw::parser::jsf;
+/// # fn main() -> Result<(),Box<dyn std::error::Error>> {
+/// let path = std::env::temp_dir().join("sdw-doc-writer.jsf");
+/// let mut writer = jsf::Writer::create(&path)?;
+/// for msg in jsf::File::open("assets/HE501_Hydro3_025.001.jsf")? {
+///     let msg = msg?;
+///     if !msg.is_unknown() {
+///         writer.write(&msg)?;
+///     }
+/// }
+/// writer.flush()?;
+/// # Ok(()) }
+/// ```
+pub struct Writer<W: io::Write + io::Seek> {
+    /// The writer to which messages are serialized
+    writer: W,
+}
+
+impl<W> Writer<W>
+where
+    W: io::Write + io::Seek,
+{
+    /// Create a JSF writer from a writer
+    pub fn new(writer: W) -> Self {
+        Writer { writer }
+    }
+
+    /// Write a message
+    ///
+    /// The message is serialized before any of it is written, and an error
+    /// is returned if its body does not take exactly the message size.
+    pub fn write(&mut self, msg: &Message) -> BinResult<()> {
+        let mut buffer = io::Cursor::new(Vec::new());
+        msg.write(&mut buffer)?;
+        let buffer = buffer.into_inner();
+        if buffer.len() as i64 != 16 + i64::from(msg.message_size) {
+            return Err(binrw::Error::AssertFail {
+                pos: self.writer.stream_position()?,
+                message: format!(
+                    "message body of {} bytes does not match message size {}",
+                    buffer.len() - 16,
+                    msg.message_size
+                ),
+            });
+        }
+        self.writer.write_all(&buffer)?;
+        Ok(())
+    }
+
+    /// Flush the underlying writer
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Return the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl Writer<std::io::BufWriter<std::fs::File>> {
+    /// Create a JSF file at the given path
+    ///
+    /// An existing file is truncated.
+    pub fn create<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        let writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+        Ok(Self::new(writer))
+    }
+}
+
 // SonarDataRecord interface
+
+/// Create a `Position` record if the longitude and latitude are known
+///
+/// The altitude is the negated depth.
+fn position<T>(
+    timestamp: OffsetDateTime,
+    longitude: Option<f64>,
+    latitude: Option<f64>,
+    depth: Option<f64>,
+) -> SonarDataRecord<T> {
+    if longitude.is_none() || latitude.is_none() {
+        return SonarDataRecord::Unknown;
+    }
+    SonarDataRecord::Position(crate::model::Position::new(
+        "unknown".to_string(),
+        timestamp,
+        longitude,
+        latitude,
+        depth.map(|depth| -depth),
+    ))
+}
+
+/// Create a `Course` record if the speed or heading is known
+fn course<T>(
+    timestamp: OffsetDateTime,
+    speed: Option<f64>,
+    heading: Option<f64>,
+) -> SonarDataRecord<T> {
+    if speed.is_none() && heading.is_none() {
+        return SonarDataRecord::Unknown;
+    }
+    SonarDataRecord::Course(crate::model::Course::new(
+        "unknown".to_string(),
+        timestamp,
+        speed,
+        heading,
+    ))
+}
+
+/// Create an `Orientation` record if any of the angles are known
+fn orientation<T>(
+    timestamp: OffsetDateTime,
+    pitch: Option<f64>,
+    roll: Option<f64>,
+    heading: Option<f64>,
+) -> SonarDataRecord<T> {
+    if pitch.is_none() && roll.is_none() && heading.is_none() {
+        return SonarDataRecord::Unknown;
+    }
+    SonarDataRecord::Orientation(crate::model::Orientation::new(
+        "unknown".to_string(),
+        timestamp,
+        pitch,
+        roll,
+        heading,
+    ))
+}
+
 impl From<Message> for SonarDataRecord<f32> {
     fn from(msg: Message) -> Self {
         let md = &msg.data;
         match md {
-            MessageType::M80 { msg: mt } => SonarDataRecord::Ping(crate::model::Ping::new(
-                "unknown".to_string(),
-                mt.timestamp(),
-                mt.mixer_frequency(),
-                mt.sampling_interval(),
-                msg.channel(),
-                mt.trace(),
-            )),
+            MessageType::M80 { msg: mt } => match (mt.epoch_timestamp(), mt.trace()) {
+                (Ok(timestamp), Ok(trace)) => SonarDataRecord::Ping(crate::model::Ping::new(
+                    "unknown".to_string(),
+                    timestamp,
+                    mt.mixer_frequency(),
+                    mt.sampling_interval(),
+                    msg.channel(),
+                    msg.subsystem_number,
+                    trace,
+                )),
+                _ => SonarDataRecord::Unknown,
+            },
             MessageType::M2020 { msg: mt } => {
                 SonarDataRecord::Orientation(crate::model::Orientation::new(
                     "unknown".to_string(),
@@ -512,7 +2876,160 @@ impl From<Message> for SonarDataRecord<f32> {
                     mt.heading(),
                 ))
             }
+            MessageType::M2002 { msg: mt } => mt.record(),
+            MessageType::M2060 { msg: mt } => match mt.depth() {
+                Some(depth) => SonarDataRecord::Position(crate::model::Position::new(
+                    "unknown".to_string(),
+                    mt.timestamp(),
+                    None,
+                    None,
+                    Some(-depth),
+                )),
+                None => SonarDataRecord::Unknown,
+            },
+            MessageType::M2080 { msg: mt } => {
+                orientation(mt.timestamp(), mt.pitch(), mt.roll(), mt.heading())
+            }
+            MessageType::M2090 { msg: mt } => {
+                position(mt.timestamp(), mt.longitude(), mt.latitude(), mt.depth())
+            }
+            MessageType::M2091 { msg: mt } => {
+                position(mt.timestamp(), mt.longitude(), mt.latitude(), mt.depth())
+            }
+            MessageType::M3000 { msg: mt } => {
+                SonarDataRecord::Bathymetry(crate::model::Bathymetry::new(
+                    "unknown".to_string(),
+                    mt.timestamp(),
+                    msg.channel(),
+                    mt.soundings(),
+                ))
+            }
+            MessageType::M3001 { msg: mt } => {
+                orientation(mt.timestamp(), mt.pitch(), mt.roll(), mt.heading())
+            }
+            MessageType::M3002 { msg: mt } => match mt.depth() {
+                Some(depth) => SonarDataRecord::Position(crate::model::Position::new(
+                    "unknown".to_string(),
+                    mt.timestamp(),
+                    None,
+                    None,
+                    Some(-depth),
+                )),
+                None => SonarDataRecord::Unknown,
+            },
+            MessageType::M3004 { msg: mt } => {
+                position(mt.timestamp(), mt.longitude(), mt.latitude(), None)
+            }
             _ => SonarDataRecord::Unknown,
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use binrw::io::Cursor;
+
+    #[test]
+    fn test_complex_samples() {
+        let mut data = vec![0x01, 0x16, 0, 0, 80, 0, 0, 20, 0, 0, 0, 0];
+        data.extend_from_slice(&248i32.to_le_bytes());
+        let mut body = vec![0u8; 240];
+        body[34..36].copy_from_slice(&1i16.to_le_bytes());
+        body[114..116].copy_from_slice(&2u16.to_le_bytes());
+        data.extend_from_slice(&body);
+        for x in [3i16, -4, -1, 0] {
+            data.extend_from_slice(&x.to_le_bytes());
+        }
+
+        let msg = Message::read(&mut Cursor::new(data)).unwrap();
+        let msg = match msg.data {
+            MessageType::M80 { msg } => msg,
+            data => panic!("Unexpected message {:?}", data),
+        };
+        assert_eq!(msg.data_format(), DataFormat::Analytic);
+        assert_eq!(
+            msg.samples(),
+            Ok(Samples::Complex(vec![
+                Complex::new(3.0, -4.0),
+                Complex::new(-1.0, 0.0)
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_undersized_write() {
+        for (message_type, size) in [
+            (2060u16, 40usize),
+            (2080, 58),
+            (2090, 244),
+            (2091, 132),
+            (2100, 32),
+            (2101, 18),
+        ] {
+            let mut data = vec![0x01, 0x16, 0, 0];
+            data.extend_from_slice(&message_type.to_le_bytes());
+            data.extend_from_slice(&[0; 6]);
+            data.extend_from_slice(&(size as i32).to_le_bytes());
+            data.extend_from_slice(&vec![0; size]);
+
+            let mut msg = Message::read(&mut Cursor::new(data)).unwrap();
+            assert!(!msg.is_unknown());
+            msg.message_size = size as i32 - 1;
+            let mut out = Cursor::new(Vec::new());
+            assert!(msg.write(&mut out).is_err());
+            assert!(out.into_inner().len() <= 16);
+        }
+    }
+
+    #[test]
+    fn test_bathymetry_write() {
+        let mut data = vec![0x01, 0x16, 0, 0];
+        data.extend_from_slice(&3000u16.to_le_bytes());
+        data.extend_from_slice(&[0; 6]);
+        data.extend_from_slice(&96i32.to_le_bytes());
+        let mut body = vec![0u8; 96];
+        body[12..14].copy_from_slice(&1u16.to_le_bytes());
+        body[40..44].copy_from_slice(&88u32.to_le_bytes());
+        body[88] = 7;
+        data.extend_from_slice(&body);
+
+        let mut msg = Message::read(&mut Cursor::new(&data)).unwrap();
+        let mut out = Cursor::new(Vec::new());
+        msg.write(&mut out).unwrap();
+        assert_eq!(data, out.into_inner());
+
+        // The samples no longer fit in the message
+        msg.message_size = 90;
+        let mut out = Cursor::new(Vec::new());
+        assert!(msg.write(&mut out).is_err());
+    }
+
+    #[test]
+    fn test_writer_message_size() {
+        for (message_type, size, bad_size) in [
+            (80u16, 242usize, 244),
+            (181, 64, 68),
+            (2002, 14, 12),
+            (2020, 44, 40),
+            (2060, 40, 36),
+            (9999, 4, 8),
+        ] {
+            let mut data = vec![0x01, 0x16, 0, 0];
+            data.extend_from_slice(&message_type.to_le_bytes());
+            data.extend_from_slice(&[0; 6]);
+            data.extend_from_slice(&(size as i32).to_le_bytes());
+            data.extend_from_slice(&vec![0; size]);
+
+            let mut msg = Message::read(&mut Cursor::new(&data)).unwrap();
+            let mut writer = Writer::new(Cursor::new(Vec::new()));
+            writer.write(&msg).unwrap();
+            assert_eq!(data, writer.into_inner().into_inner());
+
+            msg.message_size = bad_size;
+            let mut writer = Writer::new(Cursor::new(Vec::new()));
+            assert!(writer.write(&msg).is_err(), "message type {}", message_type);
+            assert!(writer.into_inner().into_inner().is_empty());
+        }
+    }
+}
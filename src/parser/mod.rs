@@ -1,4 +1,83 @@
 //! Parsing and deserializing sonar file formats
+use std::io::{self, Read};
+
 pub mod imagenex81b;
 pub mod jsf;
+pub mod nmea;
 pub mod xtf;
+
+/// The sonar file formats that SDW can parse
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Format {
+    /// Edgetech JSF
+    Jsf,
+    /// eXtended Triton Format
+    Xtf,
+    /// Imagenex 81B rotary sonar
+    Imagenex81b,
+}
+
+impl Format {
+    /// Detect the format of a file from its leading magic bytes
+    ///
+    /// The file extension is never consulted. Returns `Ok(None)`
+    /// if the leading bytes do not match any known format. The reader
+    /// is returned to its original position before returning.
+    ///
+    /// ```
+    /// # use sdw::parser::Format;
+    /// # use std::io::Cursor;
+    /// let mut reader = Cursor::new(b"\x01\x16\x10\x00");
+    /// assert_eq!(Format::detect(&mut reader).unwrap(), Some(Format::Jsf));
+    /// ```
+    pub fn detect<R: io::Read + io::Seek>(reader: &mut R) -> io::Result<Option<Format>> {
+        let start = reader.stream_position()?;
+
+        let mut magic = Vec::with_capacity(3);
+        reader.by_ref().take(3).read_to_end(&mut magic)?;
+        reader.seek(io::SeekFrom::Start(start))?;
+
+        let format = if magic.starts_with(b"\x01\x16") {
+            Some(Format::Jsf)
+        } else if magic.starts_with(b"81B") {
+            Some(Format::Imagenex81b)
+        } else if magic.starts_with(b"\x7b\x01") {
+            Some(Format::Xtf)
+        } else {
+            None
+        };
+
+        Ok(format)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::{Cursor, Seek};
+
+    #[test]
+    fn test_detect() {
+        let mut jsf = Cursor::new(b"\x01\x16\x10\x00".to_vec());
+        assert_eq!(Format::detect(&mut jsf).unwrap(), Some(Format::Jsf));
+        assert_eq!(jsf.stream_position().unwrap(), 0);
+
+        let mut xtf = Cursor::new(b"\x7b\x01".to_vec());
+        assert_eq!(Format::detect(&mut xtf).unwrap(), Some(Format::Xtf));
+
+        let mut imagenex = Cursor::new(b"81B\x00".to_vec());
+        assert_eq!(
+            Format::detect(&mut imagenex).unwrap(),
+            Some(Format::Imagenex81b)
+        );
+
+        let mut json = Cursor::new(b"{\"line\": 3}".to_vec());
+        assert_eq!(Format::detect(&mut json).unwrap(), None);
+
+        let mut text = Cursor::new(b"Survey log".to_vec());
+        assert_eq!(Format::detect(&mut text).unwrap(), None);
+
+        let mut empty = Cursor::new(Vec::new());
+        assert_eq!(Format::detect(&mut empty).unwrap(), None);
+    }
+}
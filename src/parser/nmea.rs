@@ -0,0 +1,429 @@
+//! Parsing NMEA 0183 sentences
+//!
+//! Navigation systems commonly report position, course and time as NMEA
+//! 0183 sentences, which sonar systems record alongside the sonar data.
+//! Only the sentences that carry position, course, heading or time are
+//! parsed.
+//!
+//! ```
+//! # use sdw::parser::nmea::{self, SentenceData};
+//! let sentence = nmea::parse("$GPHDT,274.07,T*03").unwrap();
+//! assert_eq!(sentence.talker, "GP");
+//! assert_eq!(sentence.data, SentenceData::Hdt { heading: Some(274.07) });
+//! ```
+use std::fmt;
+use time::{Date, Month, OffsetDateTime, PrimitiveDateTime, Time};
+
+/// The conversion factor from knots to meters per second
+const KNOTS_TO_MPS: f64 = 1852.0 / 3600.0;
+
+/// A parsed NMEA 0183 sentence
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sentence {
+    /// The talker identifier (e.g. `GP` for GPS)
+    pub talker: String,
+    /// The data fields of the sentence
+    pub data: SentenceData,
+}
+
+/// The data fields of the supported NMEA sentences
+///
+/// Latitudes and longitudes are in degrees, positive to the north and
+/// east. Fields that are empty in the sentence are `None`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SentenceData {
+    /// Global positioning system fix data
+    Gga {
+        /// The UTC time of the fix
+        time: Option<Time>,
+        /// The latitude in degrees
+        latitude: Option<f64>,
+        /// The longitude in degrees
+        longitude: Option<f64>,
+        /// The fix quality, where 0 means that the fix is invalid
+        quality: u8,
+        /// The number of satellites in use
+        satellites: Option<u8>,
+        /// The horizontal dilution of precision
+        hdop: Option<f64>,
+        /// The altitude of the antenna above mean sea level in meters
+        altitude: Option<f64>,
+    },
+    /// Geographic position
+    Gll {
+        /// The latitude in degrees
+        latitude: Option<f64>,
+        /// The longitude in degrees
+        longitude: Option<f64>,
+        /// The UTC time of the fix
+        time: Option<Time>,
+        /// Whether the data are valid
+        valid: bool,
+    },
+    /// Recommended minimum navigation information
+    Rmc {
+        /// The UTC date and time of the fix
+        timestamp: Option<OffsetDateTime>,
+        /// Whether the data are valid
+        valid: bool,
+        /// The latitude in degrees
+        latitude: Option<f64>,
+        /// The longitude in degrees
+        longitude: Option<f64>,
+        /// The speed over ground in meters per second
+        speed: Option<f64>,
+        /// The track made good in degrees east of true north
+        track: Option<f64>,
+    },
+    /// Track made good and ground speed
+    Vtg {
+        /// The track made good in degrees east of true north
+        track: Option<f64>,
+        /// The speed over ground in meters per second
+        speed: Option<f64>,
+    },
+    /// Heading from true north
+    Hdt {
+        /// The heading in degrees east of true north
+        heading: Option<f64>,
+    },
+    /// Time and date
+    Zda {
+        /// The UTC date and time
+        timestamp: Option<OffsetDateTime>,
+    },
+}
+
+/// The error returned when an NMEA sentence cannot be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseSentenceError {
+    /// The sentence does not start with `$` or has no checksum
+    Framing,
+    /// The checksum does not match the sentence
+    Checksum {
+        /// The checksum at the end of the sentence
+        expected: u8,
+        /// The checksum computed from the sentence
+        computed: u8,
+    },
+    /// The sentence type is not supported
+    Unsupported(String),
+    /// A field could not be parsed
+    InvalidField(&'static str),
+}
+
+impl fmt::Display for ParseSentenceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseSentenceError::Framing => write!(f, "Malformed NMEA sentence"),
+            ParseSentenceError::Checksum { expected, computed } => write!(
+                f,
+                "NMEA checksum mismatch: expected {:02X}, computed {:02X}",
+                expected, computed
+            ),
+            ParseSentenceError::Unsupported(s) => write!(f, "Unsupported NMEA sentence: {}", s),
+            ParseSentenceError::InvalidField(name) => write!(f, "Invalid NMEA field: {}", name),
+        }
+    }
+}
+
+impl std::error::Error for ParseSentenceError {}
+
+/// Parse an NMEA 0183 sentence
+///
+/// Leading and trailing whitespace, including the line ending, is ignored.
+/// The checksum is required and must match.
+///
+/// # Errors
+///
+/// This function returns an error if the sentence is malformed, the
+/// checksum does not match, the sentence type is not supported or a field
+/// cannot be parsed.
+pub fn parse(sentence: &str) -> Result<Sentence, ParseSentenceError> {
+    let sentence = sentence.trim();
+    let body = sentence
+        .strip_prefix('$')
+        .ok_or(ParseSentenceError::Framing)?;
+    let (body, checksum) = body.split_once('*').ok_or(ParseSentenceError::Framing)?;
+
+    let expected = u8::from_str_radix(checksum, 16).map_err(|_| ParseSentenceError::Framing)?;
+    let computed = body.bytes().fold(0, |acc, b| acc ^ b);
+    if expected != computed {
+        return Err(ParseSentenceError::Checksum { expected, computed });
+    }
+
+    let fields: Vec<&str> = body.split(',').collect();
+    let address = fields[0];
+    if address.len() != 5 || !address.is_ascii() {
+        return Err(ParseSentenceError::Unsupported(address.to_string()));
+    }
+    let (talker, kind) = address.split_at(2);
+    let field = |i: usize| fields.get(i).copied().unwrap_or("");
+
+    let data = match kind {
+        "GGA" => SentenceData::Gga {
+            time: parse_time(field(1))?,
+            latitude: parse_coordinate(field(2), field(3), "latitude")?,
+            longitude: parse_coordinate(field(4), field(5), "longitude")?,
+            quality: parse_number(field(6), "quality")?.unwrap_or(0),
+            satellites: parse_number(field(7), "satellites")?,
+            hdop: parse_number(field(8), "hdop")?,
+            altitude: parse_number(field(9), "altitude")?,
+        },
+        "GLL" => SentenceData::Gll {
+            latitude: parse_coordinate(field(1), field(2), "latitude")?,
+            longitude: parse_coordinate(field(3), field(4), "longitude")?,
+            time: parse_time(field(5))?,
+            valid: field(6) == "A",
+        },
+        "RMC" => {
+            let time = parse_time(field(1))?;
+            let date = parse_date(field(9))?;
+            SentenceData::Rmc {
+                timestamp: date
+                    .zip(time)
+                    .map(|(date, time)| PrimitiveDateTime::new(date, time).assume_utc()),
+                valid: field(2) == "A",
+                latitude: parse_coordinate(field(3), field(4), "latitude")?,
+                longitude: parse_coordinate(field(5), field(6), "longitude")?,
+                speed: parse_number::<f64>(field(7), "speed")?.map(|s| s * KNOTS_TO_MPS),
+                track: parse_number(field(8), "track")?,
+            }
+        }
+        "VTG" => {
+            // Older receivers omit the unit fields
+            let (track, knots, kmh) = if field(2) == "T" || field(2) == "M" {
+                (field(1), field(5), field(7))
+            } else {
+                (field(1), field(3), field(4))
+            };
+            let speed = match parse_number::<f64>(knots, "speed")? {
+                Some(knots) => Some(knots * KNOTS_TO_MPS),
+                None => parse_number::<f64>(kmh, "speed")?.map(|kmh| kmh / 3.6),
+            };
+            SentenceData::Vtg {
+                track: parse_number(track, "track")?,
+                speed,
+            }
+        }
+        "HDT" => SentenceData::Hdt {
+            heading: parse_number(field(1), "heading")?,
+        },
+        "ZDA" => {
+            let time = parse_time(field(1))?;
+            let day: Option<u8> = parse_number(field(2), "day")?;
+            let month: Option<u8> = parse_number(field(3), "month")?;
+            let year: Option<i32> = parse_number(field(4), "year")?;
+            let date = match (year, month, day) {
+                (Some(year), Some(month), Some(day)) => Some(
+                    Month::try_from(month)
+                        .ok()
+                        .and_then(|month| Date::from_calendar_date(year, month, day).ok())
+                        .ok_or(ParseSentenceError::InvalidField("date"))?,
+                ),
+                _ => None,
+            };
+            SentenceData::Zda {
+                timestamp: date
+                    .zip(time)
+                    .map(|(date, time)| PrimitiveDateTime::new(date, time).assume_utc()),
+            }
+        }
+        _ => return Err(ParseSentenceError::Unsupported(address.to_string())),
+    };
+
+    Ok(Sentence {
+        talker: talker.to_string(),
+        data,
+    })
+}
+
+fn parse_number<T: std::str::FromStr>(
+    field: &str,
+    name: &'static str,
+) -> Result<Option<T>, ParseSentenceError> {
+    if field.is_empty() {
+        return Ok(None);
+    }
+    field
+        .parse()
+        .map(Some)
+        .map_err(|_| ParseSentenceError::InvalidField(name))
+}
+
+/// Parse a latitude (`ddmm.mm`) or longitude (`dddmm.mm`) and its hemisphere
+fn parse_coordinate(
+    field: &str,
+    hemisphere: &str,
+    name: &'static str,
+) -> Result<Option<f64>, ParseSentenceError> {
+    let value = match parse_number::<f64>(field, name)? {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    let degrees = (value / 100.0).trunc();
+    let degrees = degrees + (value - 100.0 * degrees) / 60.0;
+    match hemisphere {
+        "N" | "E" => Ok(Some(degrees)),
+        "S" | "W" => Ok(Some(-degrees)),
+        _ => Err(ParseSentenceError::InvalidField(name)),
+    }
+}
+
+/// Parse a UTC time of day (`hhmmss.ss`)
+fn parse_time(field: &str) -> Result<Option<Time>, ParseSentenceError> {
+    if field.is_empty() {
+        return Ok(None);
+    }
+    let invalid = ParseSentenceError::InvalidField("time");
+    if field.len() < 6 || !field.is_ascii() {
+        return Err(invalid);
+    }
+    let hour = field[0..2].parse().map_err(|_| invalid.clone())?;
+    let minute = field[2..4].parse().map_err(|_| invalid.clone())?;
+    let seconds: f64 = field[4..].parse().map_err(|_| invalid.clone())?;
+    let nanos = (seconds.fract() * 1e9).round().min(999_999_999.0) as u32;
+    Time::from_hms_nano(hour, minute, seconds.trunc() as u8, nanos)
+        .map(Some)
+        .map_err(|_| invalid)
+}
+
+/// Parse a date (`ddmmyy`)
+///
+/// Two digit years from 80 are taken to be in the 20th century.
+fn parse_date(field: &str) -> Result<Option<Date>, ParseSentenceError> {
+    if field.is_empty() {
+        return Ok(None);
+    }
+    let invalid = ParseSentenceError::InvalidField("date");
+    if field.len() != 6 || !field.is_ascii() {
+        return Err(invalid);
+    }
+    let day: u8 = field[0..2].parse().map_err(|_| invalid.clone())?;
+    let month: u8 = field[2..4].parse().map_err(|_| invalid.clone())?;
+    let year: i32 = field[4..6].parse().map_err(|_| invalid.clone())?;
+    let month = Month::try_from(month).map_err(|_| invalid.clone())?;
+    let year = if year >= 80 { 1900 + year } else { 2000 + year };
+    Date::from_calendar_date(year, month, day)
+        .map(Some)
+        .map_err(|_| invalid)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use time::macros::{datetime, time};
+
+    #[test]
+    fn test_gga() {
+        let sentence =
+            parse("$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47\r\n").unwrap();
+        assert_eq!(sentence.talker, "GP");
+        match sentence.data {
+            SentenceData::Gga {
+                time,
+                latitude,
+                longitude,
+                quality,
+                satellites,
+                altitude,
+                ..
+            } => {
+                assert_eq!(time, Some(time!(12:35:19)));
+                assert!((latitude.unwrap() - 48.1173).abs() < 1e-9);
+                assert!((longitude.unwrap() - 11.516_666_666).abs() < 1e-6);
+                assert_eq!(quality, 1);
+                assert_eq!(satellites, Some(8));
+                assert_eq!(altitude, Some(545.4));
+            }
+            data => panic!("Unexpected sentence {:?}", data),
+        }
+    }
+
+    #[test]
+    fn test_rmc() {
+        let sentence =
+            parse("$GPRMC,123519,A,4807.038,N,01131.000,W,022.4,084.4,230394,003.1,W*78").unwrap();
+        match sentence.data {
+            SentenceData::Rmc {
+                timestamp,
+                valid,
+                longitude,
+                speed,
+                track,
+                ..
+            } => {
+                assert_eq!(timestamp, Some(datetime!(1994-03-23 12:35:19 UTC)));
+                assert!(valid);
+                assert!(longitude.unwrap() < 0.0);
+                assert!((speed.unwrap() - 22.4 * KNOTS_TO_MPS).abs() < 1e-9);
+                assert_eq!(track, Some(84.4));
+            }
+            data => panic!("Unexpected sentence {:?}", data),
+        }
+    }
+
+    #[test]
+    fn test_other_sentences() {
+        match parse("$GPGLL,4916.45,N,12311.12,W,225444,A,*1D")
+            .unwrap()
+            .data
+        {
+            SentenceData::Gll {
+                latitude,
+                longitude,
+                time,
+                valid,
+            } => {
+                assert!((latitude.unwrap() - 49.274_166_666).abs() < 1e-6);
+                assert!((longitude.unwrap() + 123.185_333_333).abs() < 1e-6);
+                assert_eq!(time, Some(time!(22:54:44)));
+                assert!(valid);
+            }
+            data => panic!("Unexpected sentence {:?}", data),
+        }
+        assert_eq!(
+            parse("$GPVTG,054.7,T,034.4,M,005.5,N,010.2,K*48")
+                .unwrap()
+                .data,
+            SentenceData::Vtg {
+                track: Some(54.7),
+                speed: Some(5.5 * KNOTS_TO_MPS),
+            }
+        );
+        assert_eq!(
+            parse("$GPZDA,201530.00,04,07,2002,00,00*60").unwrap().data,
+            SentenceData::Zda {
+                timestamp: Some(datetime!(2002-07-04 20:15:30 UTC)),
+            }
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            parse("$GPHDT,274.07,T*04"),
+            Err(ParseSentenceError::Checksum {
+                expected: 0x04,
+                computed: 0x03
+            })
+        );
+        assert_eq!(parse("GPHDT,274.07,T*03"), Err(ParseSentenceError::Framing));
+        assert_eq!(parse("$GPHDT,274.07,T"), Err(ParseSentenceError::Framing));
+        assert!(matches!(
+            parse("$GPGSV,1,1,00*79"),
+            Err(ParseSentenceError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_non_ascii_time() {
+        let body = "GPGGA,a\u{e9}1234,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,";
+        let checksum = body.bytes().fold(0, |acc, b| acc ^ b);
+        let sentence = format!("${}*{:02X}", body, checksum);
+        assert_eq!(
+            parse(&sentence),
+            Err(ParseSentenceError::InvalidField("time"))
+        );
+    }
+}
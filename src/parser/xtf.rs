@@ -1,6 +1,11 @@
 //! Parsing XTF files
+use crate::model::{Channel, SonarDataRecord, Sounding};
 use binrw::{binread, BinRead, BinResult};
 use std::io;
+use time::{Date, Duration, Month, OffsetDateTime, Time};
+
+/// The nominal speed of sound in m/s used to convert ranges to times
+const SOUND_SPEED: f64 = 1500.0;
 
 /// The XTFFileHeader
 #[binread]
@@ -45,10 +50,94 @@ pub struct FileHeader {
     mru_offset_yaw: f32,
     mru_offset_pitch: f32,
     mru_offset_roll: f32,
-    #[br(count = 6)]
+    #[br(count = chaninfo_count(
+        u32::from(number_of_sonar_channels)
+            + u32::from(number_of_bathy_channels)
+            + u32::from(number_of_snippet_channels)
+            + u32::from(number_of_forward_look_arrays)
+            + u32::from(number_of_echo_strength_channels)
+            + u32::from(number_of_interferometry_channels)
+    ))]
     chaninfos: Vec<ChanInfo>,
 }
 
+/// Return the number of ChanInfo entries in a file header
+///
+/// The first header block holds 6 entries. Files with more channels
+/// carry additional 1024 byte blocks of 8 entries each.
+fn chaninfo_count(number_of_channels: u32) -> usize {
+    let extra = number_of_channels.saturating_sub(6) as usize;
+    6 + extra.div_ceil(8) * 8
+}
+
+impl FileHeader {
+    /// Return the number of sonar channels
+    pub fn number_of_sonar_channels(&self) -> u16 {
+        self.number_of_sonar_channels
+    }
+
+    /// Return the number of bathymetry channels
+    pub fn number_of_bathy_channels(&self) -> u16 {
+        self.number_of_bathy_channels
+    }
+
+    /// Return the total number of channels of all kinds
+    pub fn number_of_channels(&self) -> u32 {
+        u32::from(self.number_of_sonar_channels)
+            + u32::from(self.number_of_bathy_channels)
+            + u32::from(self.number_of_snippet_channels)
+            + u32::from(self.number_of_forward_look_arrays)
+            + u32::from(self.number_of_echo_strength_channels)
+            + u32::from(self.number_of_interferometry_channels)
+    }
+
+    /// Return the navigation units
+    ///
+    /// This is 0 for coordinates in meters and 3 for longitude and
+    /// latitude in degrees.
+    pub fn nav_units(&self) -> u16 {
+        self.nav_units
+    }
+
+    /// Return the channel descriptions
+    pub fn chaninfos(&self) -> &[ChanInfo] {
+        &self.chaninfos
+    }
+
+    /// Return the sample formats of the channels
+    ///
+    /// These are indexed by channel number, and are needed to read the
+    /// packets of the file (see [`Packet`]).
+    pub fn channel_formats(&self) -> Vec<ChannelFormat> {
+        self.chaninfos.iter().map(ChanInfo::format).collect()
+    }
+
+    /// Return the channel and subsystem of a channel number
+    ///
+    /// The channel is taken from the type of the channel in its
+    /// [`ChanInfo`] (see [`ChanInfo::channel`]). Files from
+    /// multi-frequency sonars have several port and starboard channels,
+    /// and other files mix sub-bottom, bathymetry and side scan channels.
+    /// The channels are numbered as subsystems of their [`Channel`] in the
+    /// order that they appear, so that the port and starboard channels of
+    /// each frequency share a subsystem and no two channels have the same
+    /// channel and subsystem. Channel numbers without a `ChanInfo` are
+    /// `Channel::Other` with the channel number as the subsystem.
+    pub fn channel(&self, channel_number: u16) -> (Channel, u8) {
+        let index = usize::from(channel_number);
+        let info = match self.chaninfos.get(index) {
+            Some(info) => info,
+            None => return (Channel::Other, channel_number as u8),
+        };
+        let channel = info.channel();
+        let subsystem = self.chaninfos[..index]
+            .iter()
+            .filter(|other| other.channel() == channel)
+            .count();
+        (channel, subsystem as u8)
+    }
+}
+
 /// The ChanInfo struct
 #[binread]
 #[br(little)]
@@ -78,17 +167,174 @@ pub struct ChanInfo {
     sample_format: u8,
 }
 
+impl ChanInfo {
+    /// Return the type of channel
+    ///
+    /// This is 0 for sub-bottom, 1 for port, 2 for starboard and
+    /// 3 for bathymetry.
+    pub fn type_of_channel(&self) -> u8 {
+        self.type_of_channel
+    }
+
+    /// Return the number of bytes per sample
+    pub fn bytes_per_sample(&self) -> u16 {
+        self.bytes_per_sample
+    }
+
+    /// Return the channel of the channel type
+    ///
+    /// Port (1) and starboard (2) side scan channels are `Channel::Port`
+    /// and `Channel::Starboard`. Sub-bottom (0), bathymetry (3) and any
+    /// other channels are `Channel::Other`.
+    pub fn channel(&self) -> Channel {
+        match self.type_of_channel {
+            1 => Channel::Port,
+            2 => Channel::Starboard,
+            _ => Channel::Other,
+        }
+    }
+
+    /// Return the sample format code
+    ///
+    /// This is 0 for the legacy format described by the bytes per sample
+    /// and polarity, 1 for 4 byte IBM floating point, 2 for 4 byte
+    /// integers, 3 for 2 byte integers, 5 for 4 byte IEEE floating point
+    /// and 8 for 1 byte integers.
+    pub fn sample_format(&self) -> u8 {
+        self.sample_format
+    }
+
+    /// Return true if the samples are unsigned
+    pub fn unipolar(&self) -> bool {
+        self.unipolar != 0
+    }
+
+    /// Return the number of volts represented by the maximum sample value
+    pub fn volt_scale(&self) -> f64 {
+        f64::from(self.volt_scale)
+    }
+
+    /// Return the format of the samples of the channel
+    ///
+    /// Integer samples are signed unless the channel is unipolar. Unknown
+    /// sample formats fall back to the bytes per sample.
+    pub fn format(&self) -> ChannelFormat {
+        let bytes_per_sample = match self.sample_format {
+            2 => 4,
+            3 => 2,
+            8 => 1,
+            _ => self.bytes_per_sample,
+        };
+        let sample_type = match (self.sample_format, bytes_per_sample, self.unipolar()) {
+            (1, _, _) => SampleType::IbmF32,
+            (5, _, _) => SampleType::F32,
+            (_, 1, true) => SampleType::U8,
+            (_, 1, false) => SampleType::I8,
+            (_, 4, true) => SampleType::U32,
+            (_, 4, false) => SampleType::I32,
+            (_, _, false) => SampleType::I16,
+            (_, _, true) => SampleType::U16,
+        };
+        ChannelFormat {
+            sample_type,
+            volt_scale: self.volt_scale,
+        }
+    }
+
+    /// Return the name of the channel
+    pub fn channel_name(&self) -> &str {
+        self.channel_name.trim_end_matches('\0')
+    }
+
+    /// Return the frequency of the channel in hertz
+    pub fn frequency(&self) -> f64 {
+        1000.0 * f64::from(self.frequency)
+    }
+}
+
+/// The type of the samples of a channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SampleType {
+    /// Unsigned 8 bit integers
+    U8,
+    /// Signed 8 bit integers
+    I8,
+    /// Unsigned 16 bit integers
+    #[default]
+    U16,
+    /// Signed 16 bit integers
+    I16,
+    /// Unsigned 32 bit integers
+    U32,
+    /// Signed 32 bit integers
+    I32,
+    /// 32 bit IBM floating point numbers
+    IbmF32,
+    /// 32 bit IEEE floating point numbers
+    F32,
+}
+
+/// The format of the samples of a channel
+///
+/// This is found from the [`ChanInfo`] of the channel in the file header.
+/// The default is unsigned 16 bit samples without scaling.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ChannelFormat {
+    /// The type of the samples
+    pub sample_type: SampleType,
+    /// The number of volts represented by the maximum sample value
+    ///
+    /// Integer samples are not scaled if this is zero.
+    pub volt_scale: f32,
+}
+
 /// A directory of packet types
 #[binread]
-#[br(little, import {header_type: u8, num_chans_to_follow: u16})]
+#[br(little, import(
+    header_type: u8,
+    num_chans_to_follow: u16,
+    num_bytes_this_record: u32,
+    formats: &[ChannelFormat],
+))]
 #[derive(Debug, PartialEq)]
 pub enum PacketType {
     /// A packet for sidescan sonar data
     #[br(pre_assert(header_type==0))]
-    Sonar(#[br(args {num_chans_to_follow} )] PingHeader),
+    Sonar(#[br(args(num_chans_to_follow, formats))] PingHeader),
+    /// A packet with bathymetry data in a manufacturer-specific format
+    ///
+    /// This is used for types 2 and 65. The data is kept as raw bytes and
+    /// is not decoded into soundings.
+    #[br(pre_assert(header_type==2 || header_type==65))]
+    Bathy(#[br(args(num_bytes_this_record))] BathyHeader),
+    /// A packet with QPS multibeam soundings
+    #[br(pre_assert(header_type==28))]
+    QpsMultibeam(#[br(args(num_bytes_this_record))] QpsMultibeamHeader),
+    /// A packet with a text note
+    #[br(pre_assert(header_type==1))]
+    Notes(NotesHeader),
+    /// A packet with attitude data
+    #[br(pre_assert(header_type==3))]
+    Attitude(AttitudeData),
+    /// A packet with a raw serial string, such as an NMEA sentence
+    #[br(pre_assert(header_type==6))]
+    RawSerial(RawSerialHeader),
+    /// A packet with navigation data
+    #[br(pre_assert(header_type==42))]
+    Navigation(NavigationData),
+    /// A packet with gyro data
+    #[br(pre_assert(header_type==84))]
+    Gyro(GyroData),
+    /// A packet with raw position data
+    #[br(pre_assert(header_type==107))]
+    Position(PosRawNavigation),
     /// An unknown packet type.
     ///
-    /// This is used as a fallback if no other packet succeeds
+    /// This is used as a fallback if no other packet succeeds. The
+    /// high-speed sensor packets (types 11 and 15) are read as unknown
+    /// packets, since the XTF specification does not document their
+    /// layout. The same holds for the bathymetry packets of types 60 and
+    /// 61.
     Unknown,
 }
 
@@ -99,8 +345,14 @@ pub enum PacketType {
 /// and number of bytes in the packet, which all of the documented
 /// packet types do. Manufacturer-specific packets may not follow
 /// this structure, and parsing will fail for such packets.
+///
+/// The samples of sonar packets are read in the formats of their
+/// channels, which are passed as arguments (see
+/// [`FileHeader::channel_formats`]). Channels without a format are read
+/// as unsigned 16 bit samples, so [`Packet::read`] should only be used
+/// for such files.
 #[binread]
-#[br(little, magic = 64206u16)]
+#[br(little, magic = 64206u16, import(formats: &[ChannelFormat]))]
 #[derive(Debug, PartialEq)]
 pub struct Packet {
     header_type: u8,
@@ -108,7 +360,7 @@ pub struct Packet {
     #[br(pad_after = 4)]
     num_chans_to_follow: u16,
     num_bytes_this_record: u32,
-    #[br(args {header_type, num_chans_to_follow},pad_size_to=num_bytes_this_record-14)]
+    #[br(args(header_type, num_chans_to_follow, num_bytes_this_record, formats),pad_size_to=num_bytes_this_record-14)]
     header: PacketType,
 }
 
@@ -117,9 +369,734 @@ impl Packet {
     pub fn packet_name(&self) -> String {
         match self.header {
             PacketType::Sonar(_) => "Sonar".to_string(),
+            PacketType::Bathy(_) => "Bathy".to_string(),
+            PacketType::QpsMultibeam(_) => "QpsMultibeam".to_string(),
+            PacketType::Notes(_) => "Notes".to_string(),
+            PacketType::Attitude(_) => "Attitude".to_string(),
+            PacketType::RawSerial(_) => "RawSerial".to_string(),
+            PacketType::Navigation(_) => "Navigation".to_string(),
+            PacketType::Gyro(_) => "Gyro".to_string(),
+            PacketType::Position(_) => "Position".to_string(),
             PacketType::Unknown => "Unknown".to_string(),
         }
     }
+
+    /// Return the packet type
+    pub fn packet_type(&self) -> &PacketType {
+        &self.header
+    }
+
+    /// Return the sub-channel number
+    ///
+    /// For a raw serial packet this is the serial port that the string
+    /// was received on.
+    pub fn sub_channel_number(&self) -> u8 {
+        self.sub_channel_number
+    }
+
+    /// Convert the packet to SonarDataRecords
+    ///
+    /// A sonar packet is converted to a `Ping` for each of its channels.
+    /// The channel and subsystem of each ping are found from the
+    /// [`FileHeader`] of the file (see [`FileHeader::channel`]).
+    ///
+    /// A QPS multibeam packet (type 28) is converted to a `Bathymetry`.
+    /// This is the only bathymetry packet with soundings: the data of
+    /// the other bathymetry packets is in a manufacturer-specific format,
+    /// so these have no records (see [`BathyHeader::data`]).
+    ///
+    /// Attitude and gyro packets are converted to an `Orientation`. A
+    /// navigation packet is converted to a `Position`, and a raw position
+    /// packet to a `Position` and an `Orientation`. Like the coordinates
+    /// of sonar packets, positions are only returned if the navigation
+    /// units of the file are degrees.
+    ///
+    /// The position, attitude and speed in the ping header of a sonar
+    /// packet are not returned here, but by [`Packet::companion_records`],
+    /// so a locker only indexes them when it is opened with companion
+    /// records.
+    ///
+    /// Other packets, and packets with an invalid timestamp, have no
+    /// records.
+    pub fn records(&self, header: &FileHeader) -> Vec<SonarDataRecord<f32>> {
+        let ping = match &self.header {
+            PacketType::Sonar(ping) => ping,
+            PacketType::QpsMultibeam(multibeam) => return multibeam.records(),
+            PacketType::Attitude(attitude) => return attitude.records(),
+            PacketType::Navigation(navigation) => return navigation.records(header),
+            PacketType::Gyro(gyro) => return gyro.records(),
+            PacketType::Position(position) => return position.records(header),
+            _ => return Vec::new(),
+        };
+        let timestamp = match ping.timestamp() {
+            Some(timestamp) => timestamp,
+            None => return Vec::new(),
+        };
+        ping.channel_data
+            .iter()
+            .map(|chan| {
+                let (channel, subsystem) = header.channel(chan.channel_number);
+                let frequency = match header.chaninfos.get(usize::from(chan.channel_number)) {
+                    Some(info) if chan.frequency() == 0.0 => info.frequency(),
+                    _ => chan.frequency(),
+                };
+                SonarDataRecord::Ping(crate::model::Ping::new(
+                    "unknown".to_string(),
+                    timestamp,
+                    frequency,
+                    chan.sampling_interval(),
+                    channel,
+                    subsystem,
+                    chan.samples(),
+                ))
+            })
+            .collect()
+    }
+
+    /// Return the navigation records of the packet
+    ///
+    /// The ping header of a sonar packet carries the position, attitude
+    /// and speed of the sensor. These are returned as `Position`,
+    /// `Orientation` and `Course` records with the timestamp of the ping.
+    /// A `Position` is only returned if the navigation units of the file
+    /// are degrees and the coordinates are not zero. The `Course` has a
+    /// speed but no heading: the ping header only gives the heading of
+    /// the sensor, which is not its course over ground. Like the navigation
+    /// data of JSF pings, these are only indexed by a locker that is
+    /// opened with companion records. Other packets have no companion
+    /// records.
+    pub fn companion_records<T>(&self, header: &FileHeader) -> Vec<SonarDataRecord<T>> {
+        let ping = match &self.header {
+            PacketType::Sonar(ping) => ping,
+            _ => return Vec::new(),
+        };
+        let timestamp = match ping.timestamp() {
+            Some(timestamp) => timestamp,
+            None => return Vec::new(),
+        };
+
+        let mut records = Vec::new();
+        if header.nav_units == 3 {
+            if let Some((longitude, latitude)) = ping.sensor_coordinates() {
+                records.push(SonarDataRecord::Position(crate::model::Position::new(
+                    "unknown".to_string(),
+                    timestamp,
+                    Some(longitude),
+                    Some(latitude),
+                    Some(-ping.sensor_depth()),
+                )));
+            }
+        }
+        records.push(SonarDataRecord::Orientation(
+            crate::model::Orientation::new(
+                "unknown".to_string(),
+                timestamp,
+                Some(ping.sensor_pitch()),
+                Some(ping.sensor_roll()),
+                Some(ping.sensor_heading()),
+            ),
+        ));
+        records.push(SonarDataRecord::Course(crate::model::Course::new(
+            "unknown".to_string(),
+            timestamp,
+            Some(ping.sensor_speed()),
+            None,
+        )));
+        records
+    }
+}
+
+/// Return the UTC time of the calendar fields of a packet
+///
+/// Returns `None` if the fields are not a valid date and time.
+fn calendar_timestamp(
+    year: u16,
+    month: u8,
+    day: u8,
+    hour: u8,
+    minute: u8,
+    second: u8,
+) -> Option<OffsetDateTime> {
+    let month = Month::try_from(month).ok()?;
+    let date = Date::from_calendar_date(i32::from(year), month, day).ok()?;
+    let time = Time::from_hms(hour, minute, second).ok()?;
+    Some(date.with_time(time).assume_utc())
+}
+
+/// Return the time of a source epoch in seconds and microseconds
+///
+/// Returns `None` if the epoch is zero.
+fn epoch_timestamp(source_epoch: u32, microseconds: u32) -> Option<OffsetDateTime> {
+    if source_epoch == 0 {
+        return None;
+    }
+    let timestamp = OffsetDateTime::from_unix_timestamp(i64::from(source_epoch)).ok()?;
+    Some(timestamp + Duration::microseconds(i64::from(microseconds)))
+}
+
+/// Return a string from a NUL padded byte array
+fn padded_string(bytes: Vec<u8>) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+/// The size of the ping header of sonar and bathymetry packets,
+/// including the common packet fields
+const PING_HEADER_SIZE: u32 = 256;
+
+/// A packet with bathymetry data in a manufacturer-specific format
+///
+/// The packet starts with a ping header like a sonar packet, which is
+/// followed by the raw data of the bathymetric system.
+#[binread]
+#[br(little, import(num_bytes_this_record: u32))]
+#[derive(Debug, PartialEq)]
+pub struct BathyHeader {
+    #[br(args(0, &[]))]
+    ping_header: PingHeader,
+    #[br(count = num_bytes_this_record.saturating_sub(PING_HEADER_SIZE))]
+    data: Vec<u8>,
+}
+
+impl BathyHeader {
+    /// Return the ping header
+    pub fn ping_header(&self) -> &PingHeader {
+        &self.ping_header
+    }
+
+    /// Return the raw data of the bathymetric system
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+/// A sounding of a QPS multibeam packet
+#[binread]
+#[br(little)]
+#[derive(Debug, PartialEq)]
+pub struct QpsMultibeamEntry {
+    id: i32,
+    intensity: f64,
+    quality: i32,
+    two_way_travel_time: f64,
+    delta_time: f64,
+    offset_x: f64,
+    offset_y: f64,
+    #[br(pad_after = 16)]
+    offset_z: f64,
+}
+
+impl QpsMultibeamEntry {
+    /// The size of an entry in bytes
+    const SIZE: u32 = 72;
+
+    /// Return the beam number
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    /// Return the intensity in dB
+    pub fn intensity(&self) -> f64 {
+        self.intensity
+    }
+
+    /// Return the quality
+    pub fn quality(&self) -> i32 {
+        self.quality
+    }
+
+    /// Return the two way travel time in seconds
+    pub fn two_way_travel_time(&self) -> f64 {
+        self.two_way_travel_time
+    }
+
+    /// Return the time of the sounding relative to the ping in seconds
+    pub fn delta_time(&self) -> f64 {
+        self.delta_time
+    }
+
+    /// Return the across track offset in meters, positive to starboard
+    pub fn offset_x(&self) -> f64 {
+        self.offset_x
+    }
+
+    /// Return the along track offset in meters, positive forward
+    pub fn offset_y(&self) -> f64 {
+        self.offset_y
+    }
+
+    /// Return the depth in meters, positive down
+    pub fn offset_z(&self) -> f64 {
+        self.offset_z
+    }
+
+    /// Return the entry as a sounding
+    pub fn sounding(&self) -> Sounding {
+        Sounding {
+            travel_time: Some(self.two_way_travel_time),
+            angle: None,
+            depth: Some(self.offset_z),
+            across_track: Some(self.offset_x),
+            along_track: Some(self.offset_y),
+            amplitude: Some(self.intensity),
+        }
+    }
+}
+
+/// A packet with QPS multibeam soundings
+///
+/// The packet starts with a ping header like a sonar packet, which is
+/// followed by the soundings of the ping.
+#[binread]
+#[br(little, import(num_bytes_this_record: u32))]
+#[derive(Debug, PartialEq)]
+pub struct QpsMultibeamHeader {
+    #[br(args(0, &[]))]
+    ping_header: PingHeader,
+    #[br(count = num_bytes_this_record.saturating_sub(PING_HEADER_SIZE) / QpsMultibeamEntry::SIZE)]
+    entries: Vec<QpsMultibeamEntry>,
+}
+
+impl QpsMultibeamHeader {
+    /// Return the ping header
+    pub fn ping_header(&self) -> &PingHeader {
+        &self.ping_header
+    }
+
+    /// Return the soundings of the ping
+    pub fn entries(&self) -> &[QpsMultibeamEntry] {
+        &self.entries
+    }
+
+    fn records<T>(&self) -> Vec<SonarDataRecord<T>> {
+        let timestamp = match self.ping_header.timestamp() {
+            Some(timestamp) => timestamp,
+            None => return Vec::new(),
+        };
+        vec![SonarDataRecord::Bathymetry(crate::model::Bathymetry::new(
+            "unknown".to_string(),
+            timestamp,
+            Channel::Other,
+            self.entries
+                .iter()
+                .map(QpsMultibeamEntry::sounding)
+                .collect(),
+        ))]
+    }
+}
+
+/// A packet with a text note
+#[binread]
+#[br(little)]
+#[derive(Debug, PartialEq)]
+pub struct NotesHeader {
+    year: u16,
+    month: u8,
+    day: u8,
+    hour: u8,
+    minute: u8,
+    #[br(pad_after = 35)]
+    second: u8,
+    #[br(count = 200, map = padded_string)]
+    notes_text: String,
+}
+
+impl NotesHeader {
+    /// Return the time of the note
+    ///
+    /// Returns `None` if the date and time fields are invalid.
+    pub fn timestamp(&self) -> Option<OffsetDateTime> {
+        calendar_timestamp(
+            self.year,
+            self.month,
+            self.day,
+            self.hour,
+            self.minute,
+            self.second,
+        )
+    }
+
+    /// Return the text of the note
+    pub fn notes_text(&self) -> &str {
+        &self.notes_text
+    }
+}
+
+/// A packet with attitude data from a motion sensor
+#[binread]
+#[br(little)]
+#[derive(Debug, PartialEq)]
+pub struct AttitudeData {
+    #[br(pad_before = 8)]
+    epoch_microseconds: u32,
+    source_epoch: u32,
+    pitch: f32,
+    roll: f32,
+    heave: f32,
+    yaw: f32,
+    time_tag: u32,
+    heading: f32,
+    year: u16,
+    month: u8,
+    day: u8,
+    hour: u8,
+    minutes: u8,
+    seconds: u8,
+    milliseconds: u16,
+}
+
+impl AttitudeData {
+    /// Return the time of the attitude data
+    ///
+    /// This is found from the date and time fields, or from the source
+    /// epoch if the date is not set. Returns `None` if neither is valid.
+    pub fn timestamp(&self) -> Option<OffsetDateTime> {
+        match calendar_timestamp(
+            self.year,
+            self.month,
+            self.day,
+            self.hour,
+            self.minutes,
+            self.seconds,
+        ) {
+            Some(timestamp) => {
+                Some(timestamp + Duration::milliseconds(i64::from(self.milliseconds)))
+            }
+            None => epoch_timestamp(self.source_epoch, self.epoch_microseconds),
+        }
+    }
+
+    /// Return the pitch in degrees, positive bow up
+    pub fn pitch(&self) -> f64 {
+        f64::from(self.pitch)
+    }
+
+    /// Return the roll in degrees, positive port up
+    pub fn roll(&self) -> f64 {
+        f64::from(self.roll)
+    }
+
+    /// Return the heave in meters, positive up
+    pub fn heave(&self) -> f64 {
+        f64::from(self.heave)
+    }
+
+    /// Return the yaw in degrees
+    pub fn yaw(&self) -> f64 {
+        f64::from(self.yaw)
+    }
+
+    /// Return the heading in degrees
+    pub fn heading(&self) -> f64 {
+        f64::from(self.heading)
+    }
+
+    /// Return the time tag in milliseconds
+    pub fn time_tag(&self) -> u32 {
+        self.time_tag
+    }
+
+    fn records<T>(&self) -> Vec<SonarDataRecord<T>> {
+        let timestamp = match self.timestamp() {
+            Some(timestamp) => timestamp,
+            None => return Vec::new(),
+        };
+        vec![SonarDataRecord::Orientation(
+            crate::model::Orientation::new(
+                "unknown".to_string(),
+                timestamp,
+                Some(self.pitch()),
+                Some(self.roll()),
+                Some(self.heading()),
+            ),
+        )]
+    }
+}
+
+/// A packet with a raw serial string
+///
+/// The serial port that the string was received on is the sub-channel
+/// number of the packet (see [`Packet::sub_channel_number`]).
+#[binread]
+#[br(little)]
+#[derive(Debug, PartialEq)]
+pub struct RawSerialHeader {
+    year: u16,
+    month: u8,
+    day: u8,
+    hour: u8,
+    minute: u8,
+    second: u8,
+    hseconds: u8,
+    julian_day: u16,
+    time_tag: u32,
+    string_size: u16,
+    #[br(count = string_size, map = padded_string)]
+    raw_ascii_data: String,
+}
+
+impl RawSerialHeader {
+    /// Return the time the string was received
+    ///
+    /// Returns `None` if the date and time fields are invalid.
+    pub fn timestamp(&self) -> Option<OffsetDateTime> {
+        let timestamp = calendar_timestamp(
+            self.year,
+            self.month,
+            self.day,
+            self.hour,
+            self.minute,
+            self.second,
+        )?;
+        Some(timestamp + Duration::milliseconds(10 * i64::from(self.hseconds)))
+    }
+
+    /// Return the raw string
+    pub fn raw_ascii_data(&self) -> &str {
+        &self.raw_ascii_data
+    }
+}
+
+/// A packet with navigation data
+#[binread]
+#[br(little)]
+#[derive(Debug, PartialEq)]
+pub struct NavigationData {
+    year: u16,
+    month: u8,
+    day: u8,
+    hour: u8,
+    minute: u8,
+    second: u8,
+    microseconds: u32,
+    source_epoch: u32,
+    time_tag: u32,
+    raw_y_coordinate: f64,
+    raw_x_coordinate: f64,
+    raw_altitude: f64,
+    time_flag: u8,
+}
+
+impl NavigationData {
+    /// Return the time of the navigation data
+    ///
+    /// This is found from the date and time fields, or from the source
+    /// epoch if the date is not set. Returns `None` if neither is valid.
+    pub fn timestamp(&self) -> Option<OffsetDateTime> {
+        match calendar_timestamp(
+            self.year,
+            self.month,
+            self.day,
+            self.hour,
+            self.minute,
+            self.second,
+        ) {
+            Some(timestamp) => {
+                Some(timestamp + Duration::microseconds(i64::from(self.microseconds)))
+            }
+            None => epoch_timestamp(self.source_epoch, self.microseconds),
+        }
+    }
+
+    /// Return the coordinates
+    ///
+    /// These are the longitude and latitude in degrees, or the easting
+    /// and northing in meters, depending on the navigation units of the
+    /// file.
+    pub fn coordinates(&self) -> (f64, f64) {
+        (self.raw_x_coordinate, self.raw_y_coordinate)
+    }
+
+    /// Return the altitude in meters
+    pub fn altitude(&self) -> f64 {
+        self.raw_altitude
+    }
+
+    /// Return the time tag in milliseconds
+    pub fn time_tag(&self) -> u32 {
+        self.time_tag
+    }
+
+    fn records<T>(&self, header: &FileHeader) -> Vec<SonarDataRecord<T>> {
+        let timestamp = match self.timestamp() {
+            Some(timestamp) if header.nav_units == 3 => timestamp,
+            _ => return Vec::new(),
+        };
+        let (longitude, latitude) = self.coordinates();
+        vec![SonarDataRecord::Position(crate::model::Position::new(
+            "unknown".to_string(),
+            timestamp,
+            Some(longitude),
+            Some(latitude),
+            Some(self.altitude()),
+        ))]
+    }
+}
+
+/// A packet with gyro data
+#[binread]
+#[br(little)]
+#[derive(Debug, PartialEq)]
+pub struct GyroData {
+    year: u16,
+    month: u8,
+    day: u8,
+    hour: u8,
+    minute: u8,
+    second: u8,
+    microseconds: u32,
+    source_epoch: u32,
+    time_tag: u32,
+    gyro: f32,
+    time_flag: u8,
+}
+
+impl GyroData {
+    /// Return the time of the gyro data
+    ///
+    /// This is found from the date and time fields, or from the source
+    /// epoch if the date is not set. Returns `None` if neither is valid.
+    pub fn timestamp(&self) -> Option<OffsetDateTime> {
+        match calendar_timestamp(
+            self.year,
+            self.month,
+            self.day,
+            self.hour,
+            self.minute,
+            self.second,
+        ) {
+            Some(timestamp) => {
+                Some(timestamp + Duration::microseconds(i64::from(self.microseconds)))
+            }
+            None => epoch_timestamp(self.source_epoch, self.microseconds),
+        }
+    }
+
+    /// Return the heading in degrees
+    pub fn gyro(&self) -> f64 {
+        f64::from(self.gyro)
+    }
+
+    /// Return the time tag in milliseconds
+    pub fn time_tag(&self) -> u32 {
+        self.time_tag
+    }
+
+    fn records<T>(&self) -> Vec<SonarDataRecord<T>> {
+        let timestamp = match self.timestamp() {
+            Some(timestamp) => timestamp,
+            None => return Vec::new(),
+        };
+        vec![SonarDataRecord::Orientation(
+            crate::model::Orientation::new(
+                "unknown".to_string(),
+                timestamp,
+                None,
+                None,
+                Some(self.gyro()),
+            ),
+        )]
+    }
+}
+
+/// A packet with raw position and attitude data
+#[binread]
+#[br(little)]
+#[derive(Debug, PartialEq)]
+pub struct PosRawNavigation {
+    year: u16,
+    month: u8,
+    day: u8,
+    hour: u8,
+    minutes: u8,
+    seconds: u8,
+    tenths_of_milliseconds: u16,
+    raw_y_coordinate: f64,
+    raw_x_coordinate: f64,
+    raw_altitude: f64,
+    pitch: f32,
+    roll: f32,
+    heave: f32,
+    heading: f32,
+}
+
+impl PosRawNavigation {
+    /// Return the time of the position
+    ///
+    /// Returns `None` if the date and time fields are invalid.
+    pub fn timestamp(&self) -> Option<OffsetDateTime> {
+        let timestamp = calendar_timestamp(
+            self.year,
+            self.month,
+            self.day,
+            self.hour,
+            self.minutes,
+            self.seconds,
+        )?;
+        Some(timestamp + Duration::microseconds(100 * i64::from(self.tenths_of_milliseconds)))
+    }
+
+    /// Return the coordinates
+    ///
+    /// These are the longitude and latitude in degrees, or the easting
+    /// and northing in meters, depending on the navigation units of the
+    /// file.
+    pub fn coordinates(&self) -> (f64, f64) {
+        (self.raw_x_coordinate, self.raw_y_coordinate)
+    }
+
+    /// Return the altitude in meters
+    pub fn altitude(&self) -> f64 {
+        self.raw_altitude
+    }
+
+    /// Return the pitch in degrees
+    pub fn pitch(&self) -> f64 {
+        f64::from(self.pitch)
+    }
+
+    /// Return the roll in degrees
+    pub fn roll(&self) -> f64 {
+        f64::from(self.roll)
+    }
+
+    /// Return the heave in meters
+    pub fn heave(&self) -> f64 {
+        f64::from(self.heave)
+    }
+
+    /// Return the heading in degrees
+    pub fn heading(&self) -> f64 {
+        f64::from(self.heading)
+    }
+
+    fn records<T>(&self, header: &FileHeader) -> Vec<SonarDataRecord<T>> {
+        let timestamp = match self.timestamp() {
+            Some(timestamp) => timestamp,
+            None => return Vec::new(),
+        };
+        let mut records = Vec::new();
+        if header.nav_units == 3 {
+            let (longitude, latitude) = self.coordinates();
+            records.push(SonarDataRecord::Position(crate::model::Position::new(
+                "unknown".to_string(),
+                timestamp,
+                Some(longitude),
+                Some(latitude),
+                Some(self.altitude()),
+            )));
+        }
+        records.push(SonarDataRecord::Orientation(
+            crate::model::Orientation::new(
+                "unknown".to_string(),
+                timestamp,
+                Some(self.pitch()),
+                Some(self.roll()),
+                Some(self.heading()),
+            ),
+        ));
+        records
+    }
 }
 
 /// A header describing ping-specific information
@@ -128,7 +1105,7 @@ impl Packet {
 /// data for the ping are stored in a Vec<PingChanHeader> with
 /// one element for each channel.
 #[binread]
-#[br(little,import {num_chans_to_follow: u16})]
+#[br(little,import(num_chans_to_follow: u16, formats: &[ChannelFormat]))]
 #[derive(Debug, PartialEq)]
 pub struct PingHeader {
     year: u16,
@@ -202,16 +1179,115 @@ pub struct PingHeader {
     optional_offset: u32,
     #[br(pad_after = 6)]
     cable_out_hundredths: u8,
-    #[br(count=num_chans_to_follow)]
+    #[br(count=num_chans_to_follow, args {inner: (formats,)})]
     channel_data: Vec<PingChanHeader>,
 }
 
+impl PingHeader {
+    /// Return the timestamp of the ping
+    ///
+    /// Returns `None` if the date and time fields are invalid.
+    pub fn timestamp(&self) -> Option<OffsetDateTime> {
+        let timestamp = calendar_timestamp(
+            self.year,
+            self.month,
+            self.day,
+            self.hour,
+            self.minute,
+            self.second,
+        )?;
+        Some(timestamp + Duration::milliseconds(10 * i64::from(self.hseconds)))
+    }
+
+    /// Return the ping number
+    pub fn ping_number(&self) -> u32 {
+        self.ping_number
+    }
+
+    /// Return the sound velocity in m/s
+    pub fn sound_velocity(&self) -> f64 {
+        f64::from(self.sound_velocity)
+    }
+
+    /// Return the coordinates of the ship
+    ///
+    /// These are the longitude and latitude in degrees, or the easting
+    /// and northing in meters, depending on the navigation units of the
+    /// file. Returns `None` if both coordinates are zero.
+    pub fn ship_coordinates(&self) -> Option<(f64, f64)> {
+        (self.ship_x_coordinate != 0.0 || self.ship_y_coordinate != 0.0)
+            .then_some((self.ship_x_coordinate, self.ship_y_coordinate))
+    }
+
+    /// Return the coordinates of the sensor
+    ///
+    /// These are in the same units as [`PingHeader::ship_coordinates`].
+    pub fn sensor_coordinates(&self) -> Option<(f64, f64)> {
+        (self.sensor_x_coordinate != 0.0 || self.sensor_y_coordinate != 0.0)
+            .then_some((self.sensor_x_coordinate, self.sensor_y_coordinate))
+    }
+
+    /// Return the heading of the ship in degrees
+    pub fn ship_gyro(&self) -> f64 {
+        f64::from(self.ship_gyro)
+    }
+
+    /// Return the speed of the ship in m/s
+    pub fn ship_speed(&self) -> f64 {
+        f64::from(self.ship_speed) * 1852.0 / 3600.0
+    }
+
+    /// Return the speed of the sensor in m/s
+    pub fn sensor_speed(&self) -> f64 {
+        f64::from(self.sensor_speed) * 1852.0 / 3600.0
+    }
+
+    /// Return the depth of the sensor in meters
+    pub fn sensor_depth(&self) -> f64 {
+        f64::from(self.sensor_depth)
+    }
+
+    /// Return the altitude of the sensor above the seafloor in meters
+    pub fn sensor_primary_altitude(&self) -> f64 {
+        f64::from(self.sensor_primary_altitude)
+    }
+
+    /// Return the pitch of the sensor in degrees
+    ///
+    /// Nose up is positive
+    pub fn sensor_pitch(&self) -> f64 {
+        f64::from(self.sensor_pitch)
+    }
+
+    /// Return the roll of the sensor in degrees
+    ///
+    /// Port up is positive
+    pub fn sensor_roll(&self) -> f64 {
+        f64::from(self.sensor_roll)
+    }
+
+    /// Return the heading of the sensor in degrees
+    pub fn sensor_heading(&self) -> f64 {
+        f64::from(self.sensor_heading)
+    }
+
+    /// Return the layback of the sensor behind the ship in meters
+    pub fn layback(&self) -> f64 {
+        f64::from(self.layback)
+    }
+
+    /// Return the data of the channels of the ping
+    pub fn channel_data(&self) -> &[PingChanHeader] {
+        &self.channel_data
+    }
+}
+
 /// A header describing ping- and channel-specific information
 ///
 /// The actual sonar return data are stored as a SonarData wrapper
-/// in the data field.
+/// in the data field, in the format of the channel.
 #[binread]
-#[br(little)]
+#[br(little, import(formats: &[ChannelFormat]))]
 #[derive(Debug, PartialEq)]
 pub struct PingChanHeader {
     channel_number: u16,
@@ -238,27 +1314,133 @@ pub struct PingChanHeader {
     fixed_vsop: f32,
     #[br(pad_after = 4)]
     weight: i16,
-    #[br(args {bytes_per_sample: 2, num_samples})]
+    #[br(calc = formats.get(usize::from(channel_number)).copied().unwrap_or_default())]
+    format: ChannelFormat,
+    #[br(args {sample_type: format.sample_type, num_samples})]
     data: SonarData,
 }
 
 #[binread]
-#[br(little, import {bytes_per_sample: u16, num_samples: u32})]
+#[br(little, import {sample_type: SampleType, num_samples: u32})]
 #[derive(Debug, PartialEq)]
 /// An enum to dispatch different sonar data types
-///
-/// Warning: the bytes_per_sample field is currently hardcoded to 2,
-/// so the data will always be 16 bit.
 pub enum SonarData {
-    /// 8 bit sonar data
-    #[br(pre_assert(bytes_per_sample==1))]
+    /// Unsigned 8 bit sonar data
+    #[br(pre_assert(sample_type==SampleType::U8))]
     U8(#[br(count=num_samples)] Vec<u8>),
-    /// 16 bit sonar data
-    #[br(pre_assert(bytes_per_sample==2))]
+    /// Signed 8 bit sonar data
+    #[br(pre_assert(sample_type==SampleType::I8))]
+    I8(#[br(count=num_samples)] Vec<i8>),
+    /// Unsigned 16 bit sonar data
+    #[br(pre_assert(sample_type==SampleType::U16))]
     U16(#[br(count=num_samples)] Vec<u16>),
-    /// 32 bit sonar data
-    #[br(pre_assert(bytes_per_sample==4))]
+    /// Signed 16 bit sonar data
+    #[br(pre_assert(sample_type==SampleType::I16))]
+    I16(#[br(count=num_samples)] Vec<i16>),
+    /// Unsigned 32 bit sonar data
+    #[br(pre_assert(sample_type==SampleType::U32))]
     U32(#[br(count=num_samples)] Vec<u32>),
+    /// Signed 32 bit sonar data
+    #[br(pre_assert(sample_type==SampleType::I32))]
+    I32(#[br(count=num_samples)] Vec<i32>),
+    /// IBM floating point sonar data, converted to IEEE floating point
+    #[br(pre_assert(sample_type==SampleType::IbmF32))]
+    IbmF32(
+        #[br(count=num_samples, map = |raw: Vec<u32>| raw.into_iter().map(ibm_to_f32).collect())]
+        Vec<f32>,
+    ),
+    /// IEEE floating point sonar data
+    #[br(pre_assert(sample_type==SampleType::F32))]
+    F32(#[br(count=num_samples)] Vec<f32>),
+}
+
+/// Convert a 32 bit IBM floating point number to an IEEE float
+fn ibm_to_f32(x: u32) -> f32 {
+    let sign = if x >> 31 == 1 { -1.0 } else { 1.0 };
+    let exponent = ((x >> 24) & 0x7f) as i32 - 64;
+    let fraction = f64::from(x & 0x00ff_ffff) / f64::from(1 << 24);
+    (sign * fraction * 16f64.powi(exponent)) as f32
+}
+
+impl PingChanHeader {
+    /// Return the channel number
+    pub fn channel_number(&self) -> u16 {
+        self.channel_number
+    }
+
+    /// Return the slant range in meters
+    pub fn slant_range(&self) -> f64 {
+        f64::from(self.slant_range)
+    }
+
+    /// Return the number of samples
+    pub fn num_samples(&self) -> u32 {
+        self.num_samples
+    }
+
+    /// Return the center frequency of the channel in hertz
+    pub fn frequency(&self) -> f64 {
+        1000.0 * f64::from(self.frequency)
+    }
+
+    /// Return the sampling interval in seconds
+    ///
+    /// This is the two way travel time to the slant range at the nominal
+    /// speed of sound divided by the number of samples.
+    pub fn sampling_interval(&self) -> f64 {
+        if self.num_samples == 0 {
+            return 0.0;
+        }
+        2.0 * f64::from(self.slant_range) / SOUND_SPEED / f64::from(self.num_samples)
+    }
+
+    /// Return the format of the samples
+    pub fn format(&self) -> ChannelFormat {
+        self.format
+    }
+
+    /// Return the sonar data
+    pub fn data(&self) -> &SonarData {
+        &self.data
+    }
+
+    /// Return the samples as floating point numbers in volts
+    ///
+    /// Integer samples are scaled so that the maximum sample value is the
+    /// volt scale of the channel. They are not scaled if the volt scale
+    /// is zero. Floating point samples are never scaled.
+    pub fn samples(&self) -> Vec<f32> {
+        let samples = self.data.samples();
+        let full_scale = match self.data {
+            SonarData::U8(_) => f32::from(u8::MAX),
+            SonarData::I8(_) => f32::from(i8::MAX),
+            SonarData::U16(_) => f32::from(u16::MAX),
+            SonarData::I16(_) => f32::from(i16::MAX),
+            SonarData::U32(_) => u32::MAX as f32,
+            SonarData::I32(_) => i32::MAX as f32,
+            SonarData::IbmF32(_) | SonarData::F32(_) => return samples,
+        };
+        if self.format.volt_scale == 0.0 {
+            return samples;
+        }
+        let scale = self.format.volt_scale / full_scale;
+        samples.into_iter().map(|x| x * scale).collect()
+    }
+}
+
+impl SonarData {
+    /// Return the samples as floating point numbers
+    pub fn samples(&self) -> Vec<f32> {
+        match self {
+            SonarData::U8(data) => data.iter().map(|&x| f32::from(x)).collect(),
+            SonarData::I8(data) => data.iter().map(|&x| f32::from(x)).collect(),
+            SonarData::U16(data) => data.iter().map(|&x| f32::from(x)).collect(),
+            SonarData::I16(data) => data.iter().map(|&x| f32::from(x)).collect(),
+            SonarData::U32(data) => data.iter().map(|&x| x as f32).collect(),
+            SonarData::I32(data) => data.iter().map(|&x| x as f32).collect(),
+            SonarData::IbmF32(data) | SonarData::F32(data) => data.clone(),
+        }
+    }
 }
 
 /// A representation of an XTF file on disk
@@ -267,6 +1449,7 @@ where
     T: io::Read + io::Seek,
 {
     header: FileHeader,
+    formats: Vec<ChannelFormat>,
     reader: T,
 }
 
@@ -277,7 +1460,12 @@ where
     /// Create an XTF file from a reader
     pub fn new(mut reader: T) -> Self {
         let header = FileHeader::read(&mut reader).expect("Unable to read XTF file header");
-        File { header, reader }
+        let formats = header.channel_formats();
+        File {
+            header,
+            formats,
+            reader,
+        }
     }
 
     /// Return a reference to the FileHeader
@@ -308,7 +1496,7 @@ impl<T: io::Read + io::Seek> Iterator for File<T> {
     type Item = BinResult<Packet>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let res = Packet::read(&mut self.reader);
+        let res = Packet::read_args(&mut self.reader, (&self.formats,));
         match res {
             Ok(msg) => Some(Ok(msg)),
             Err(e) => {
@@ -0,0 +1,78 @@
+//! Fixtures shared by the integration tests
+//!
+//! Each test binary only uses some of these.
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+
+/// Build a JSF message with the given type and body
+///
+/// The message size is the length of the body, and the subsystem and
+/// channel numbers are zero.
+pub fn jsf_message(message_type: u16, body: &[u8]) -> Vec<u8> {
+    let mut msg = vec![0x01, 0x16, 0, 0];
+    msg.extend_from_slice(&message_type.to_le_bytes());
+    msg.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+    msg.extend_from_slice(&(body.len() as i32).to_le_bytes());
+    msg.extend_from_slice(body);
+    msg
+}
+
+/// Set the message size in the header of a JSF message
+pub fn set_message_size(msg: &mut [u8], message_size: i32) {
+    msg[12..16].copy_from_slice(&message_size.to_le_bytes());
+}
+
+/// Build a JSF sonar data message (type 80) from the low frequency side
+/// scan subsystem
+pub fn jsf_ping(channel: u8, body: &[u8]) -> Vec<u8> {
+    let mut msg = jsf_message(80, body);
+    msg[7] = 20;
+    msg[8] = channel;
+    msg
+}
+
+/// Build the body of a sonar data message with the given trace
+///
+/// Only the time since the epoch, the milliseconds today and the number
+/// of envelope samples in the trace are set.
+pub fn ping_body(time: i32, milliseconds_today: u32, trace: &[u8]) -> Vec<u8> {
+    let mut body = vec![0u8; 240];
+    body[0..4].copy_from_slice(&time.to_le_bytes());
+    body[114..116].copy_from_slice(&((trace.len() / 2) as u16).to_le_bytes());
+    body[200..204].copy_from_slice(&milliseconds_today.to_le_bytes());
+    body.extend_from_slice(trace);
+    body
+}
+
+/// Build the body of an NMEA string message (type 2002)
+///
+/// The sentence is 250 ms after `time`.
+pub fn nmea_body(time: i32, sentence: &str) -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend_from_slice(&time.to_le_bytes());
+    body.extend_from_slice(&250i32.to_le_bytes());
+    body.extend_from_slice(&[1, 0, 0, 0]);
+    body.extend_from_slice(sentence.as_bytes());
+    body
+}
+
+/// Return the offsets of the messages in a JSF file
+pub fn message_offsets(data: &[u8]) -> Vec<usize> {
+    let mut offsets = Vec::new();
+    let mut offset = 0;
+    while offset + 16 <= data.len() {
+        offsets.push(offset);
+        let size = i32::from_le_bytes(data[offset + 12..offset + 16].try_into().unwrap());
+        offset += 16 + size as usize;
+    }
+    offsets
+}
+
+/// Create an empty locker directory unique to the test
+pub fn locker_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("sdw-locker-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
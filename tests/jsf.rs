@@ -1,5 +1,11 @@
+mod common;
+
 use binrw::io::BufReader;
+use common::{jsf_message, jsf_ping, message_offsets, nmea_body, ping_body, set_message_size};
+use sdw::model::SonarDataRecord;
 use sdw::parser::jsf;
+use std::io::Cursor;
+use time::{Duration, OffsetDateTime};
 
 #[test]
 fn read_file() -> Result<(), Box<dyn std::error::Error>> {
@@ -11,3 +17,817 @@ fn read_file() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+/// Build a JSF NMEA string message (type 2002)
+fn jsf_nmea(time: i32, sentence: &str) -> Vec<u8> {
+    jsf_message(2002, &nmea_body(time, sentence))
+}
+
+#[test]
+fn nmea_records() -> Result<(), Box<dyn std::error::Error>> {
+    let mut data = jsf_nmea(
+        1_600_000_000,
+        "$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47\r\n",
+    );
+    data.extend(jsf_nmea(
+        1_600_000_000,
+        "$GPVTG,054.7,T,034.4,M,005.5,N,010.2,K*48\r\n",
+    ));
+    data.extend(jsf_nmea(1_600_000_000, "$GPHDT,274.07,T*04\r\n"));
+
+    let records: Vec<SonarDataRecord<f32>> = jsf::File::new(Cursor::new(data))
+        .map(|msg| msg.map(SonarDataRecord::from))
+        .collect::<Result<_, _>>()?;
+    assert_eq!(3, records.len());
+
+    match &records[0] {
+        SonarDataRecord::Position(position) => {
+            assert_eq!(
+                OffsetDateTime::from_unix_timestamp(1_600_000_000)? + Duration::milliseconds(250),
+                position.timestamp
+            );
+            assert!((position.latitude.unwrap() - 48.1173).abs() < 1e-9);
+            assert_eq!(Some(545.4), position.altitude);
+        }
+        rec => panic!("Unexpected record {:?}", rec),
+    }
+    match &records[1] {
+        SonarDataRecord::Course(course) => assert_eq!(Some(54.7), course.heading),
+        rec => panic!("Unexpected record {:?}", rec),
+    }
+    // Sentences with a bad checksum are not decoded
+    assert!(matches!(records[2], SonarDataRecord::Unknown));
+
+    Ok(())
+}
+
+/// Build a JSF sonar data message (type 80) with embedded navigation data
+fn jsf_nav_ping(channel: u8) -> Vec<u8> {
+    let mut data = ping_body(1_600_000_000, 0, &[]);
+    // Position, course, speed, heading, pitch/roll, altitude and depth are valid
+    data[30..32].copy_from_slice(&0x026fu16.to_le_bytes());
+    // 45.5 degrees west, 30.25 degrees north in ten thousandths of a minute
+    data[80..84].copy_from_slice(&(-27_300_000i32).to_le_bytes());
+    data[84..88].copy_from_slice(&18_150_000i32.to_le_bytes());
+    data[88..90].copy_from_slice(&2i16.to_le_bytes());
+    data[136..140].copy_from_slice(&12_500i32.to_le_bytes());
+    data[144..148].copy_from_slice(&8_000i32.to_le_bytes());
+    data[172..174].copy_from_slice(&9_000u16.to_le_bytes());
+    data[174..176].copy_from_slice(&8_192i16.to_le_bytes());
+    data[192..194].copy_from_slice(&92i16.to_le_bytes());
+    data[194..196].copy_from_slice(&40i16.to_le_bytes());
+    jsf_ping(channel, &data)
+}
+
+#[test]
+fn companion_records() -> Result<(), Box<dyn std::error::Error>> {
+    let mut messages = jsf::File::new(Cursor::new([jsf_nav_ping(0), jsf_nav_ping(1)].concat()));
+
+    let port = messages.next().unwrap()?;
+    let records: Vec<SonarDataRecord<f32>> = port.companion_records();
+    assert_eq!(3, records.len());
+    match &records[0] {
+        SonarDataRecord::Position(position) => {
+            assert!((position.longitude.unwrap() + 45.5).abs() < 1e-9);
+            assert!((position.latitude.unwrap() - 30.25).abs() < 1e-9);
+            assert_eq!(Some(-12.5), position.altitude);
+        }
+        rec => panic!("Unexpected record {:?}", rec),
+    }
+    match &records[1] {
+        SonarDataRecord::Orientation(orientation) => {
+            assert_eq!(Some(45.0), orientation.pitch);
+            assert_eq!(Some(90.0), orientation.heading);
+        }
+        rec => panic!("Unexpected record {:?}", rec),
+    }
+    match &records[2] {
+        SonarDataRecord::Course(course) => {
+            assert_eq!(Some(92.0), course.heading);
+            assert!((course.speed.unwrap() - 4.0 * 1852.0 / 3600.0).abs() < 1e-9);
+        }
+        rec => panic!("Unexpected record {:?}", rec),
+    }
+
+    // The starboard message carries the same navigation data
+    let starboard = messages.next().unwrap()?;
+    assert!(starboard.companion_records::<f32>().is_empty());
+
+    Ok(())
+}
+
+/// Build a JSF sonar data message (type 80) with the given sample data
+fn jsf_ping_data(data_format: i16, samples: u16, trace: &[u8]) -> Vec<u8> {
+    let mut data = ping_body(1_600_000_000, 0, trace);
+    data[34..36].copy_from_slice(&data_format.to_le_bytes());
+    data[114..116].copy_from_slice(&samples.to_le_bytes());
+    // A weighting factor of 1 halves every sample
+    data[168..170].copy_from_slice(&1i16.to_le_bytes());
+    jsf_ping(0, &data)
+}
+
+fn ping_trace(msg: Vec<u8>) -> Result<Vec<f32>, Box<dyn std::error::Error>> {
+    let msg = jsf::File::new(Cursor::new(msg)).next().unwrap()?;
+    match SonarDataRecord::from(msg) {
+        SonarDataRecord::Ping(ping) => Ok(ping.data),
+        rec => panic!("Unexpected record {:?}", rec),
+    }
+}
+
+#[test]
+fn data_formats() -> Result<(), Box<dyn std::error::Error>> {
+    let envelope = [4u16, 65534].map(u16::to_le_bytes).concat();
+    assert_eq!(
+        vec![2.0, 32767.0],
+        ping_trace(jsf_ping_data(0, 2, &envelope))?
+    );
+
+    // A trace that does not match the data format is an error
+    let msg = read_message(jsf_ping_data(0, 2, &[4, 254]))?;
+    let error = msg.sonar_data().unwrap().samples().unwrap_err();
+    assert_eq!(jsf::DataFormat::Envelope, error.data_format);
+    assert_eq!(2, error.size);
+    assert!(matches!(
+        SonarDataRecord::<f32>::from(msg),
+        SonarDataRecord::Unknown
+    ));
+    assert!(read_message(jsf_ping_data(1, 2, &envelope))?
+        .sonar_data()
+        .unwrap()
+        .trace()
+        .is_err());
+
+    let raw = [-4i16, 6].map(i16::to_le_bytes).concat();
+    assert_eq!(vec![-2.0, 3.0], ping_trace(jsf_ping_data(2, 2, &raw))?);
+
+    // Analytic samples are converted to their magnitudes
+    let analytic = [6i16, -8, 0, 2].map(i16::to_le_bytes).concat();
+    assert_eq!(vec![5.0, 1.0], ping_trace(jsf_ping_data(1, 2, &analytic))?);
+    assert_eq!(vec![5.0, 1.0], ping_trace(jsf_ping_data(9, 2, &analytic))?);
+
+    Ok(())
+}
+
+fn read_message(msg: Vec<u8>) -> Result<jsf::Message, Box<dyn std::error::Error>> {
+    Ok(jsf::File::new(Cursor::new(msg)).next().unwrap()?)
+}
+
+#[test]
+fn situation() -> Result<(), Box<dyn std::error::Error>> {
+    let mut body = vec![0u8; 260];
+    body[0..4].copy_from_slice(&1_600_000_000i32.to_le_bytes());
+    // The microsecond timestamp, latitude, longitude, depth and heading are valid
+    body[12..16].copy_from_slice(&0x1fu32.to_le_bytes());
+    body[20..28].copy_from_slice(&1_600_000_000_250_000u64.to_le_bytes());
+    body[28..36].copy_from_slice(&30.25f64.to_le_bytes());
+    body[36..44].copy_from_slice(&(-45.5f64).to_le_bytes());
+    body[44..52].copy_from_slice(&12.5f64.to_le_bytes());
+    body[52..60].copy_from_slice(&90.0f64.to_le_bytes());
+
+    let msg = read_message(jsf_message(2090, &body))?;
+    let companions: Vec<SonarDataRecord<f32>> = msg.companion_records();
+    match SonarDataRecord::<f32>::from(msg) {
+        SonarDataRecord::Position(position) => {
+            assert_eq!(
+                OffsetDateTime::from_unix_timestamp(1_600_000_000)? + Duration::milliseconds(250),
+                position.timestamp
+            );
+            assert_eq!(Some(30.25), position.latitude);
+            assert_eq!(Some(-45.5), position.longitude);
+            assert_eq!(Some(-12.5), position.altitude);
+        }
+        rec => panic!("Unexpected record {:?}", rec),
+    }
+    match &companions[..] {
+        [SonarDataRecord::Orientation(orientation)] => {
+            assert_eq!(None, orientation.pitch);
+            assert_eq!(Some(90.0), orientation.heading);
+        }
+        recs => panic!("Unexpected records {:?}", recs),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn doppler_velocity_log() -> Result<(), Box<dyn std::error::Error>> {
+    let mut body = vec![0u8; 80];
+    body[0..4].copy_from_slice(&1_600_000_000i32.to_le_bytes());
+    // Bottom velocity in earth coordinates, heading and pitch are present
+    body[12..16].copy_from_slice(&0xc1u32.to_le_bytes());
+    body[32..34].copy_from_slice(&1000i16.to_le_bytes());
+    body[34..36].copy_from_slice(&(-1000i16).to_le_bytes());
+    body[46..48].copy_from_slice(&250i16.to_le_bytes());
+    body[50..52].copy_from_slice(&13_500u16.to_le_bytes());
+
+    let msg = read_message(jsf_message(2080, &body))?;
+    let companions: Vec<SonarDataRecord<f32>> = msg.companion_records();
+    match SonarDataRecord::<f32>::from(msg) {
+        SonarDataRecord::Orientation(orientation) => {
+            assert_eq!(Some(2.5), orientation.pitch);
+            assert_eq!(None, orientation.roll);
+            assert_eq!(Some(135.0), orientation.heading);
+        }
+        rec => panic!("Unexpected record {:?}", rec),
+    }
+    match &companions[..] {
+        [SonarDataRecord::Course(course)] => {
+            assert!((course.speed.unwrap() - 2.0f64.sqrt()).abs() < 1e-9);
+            assert!((course.heading.unwrap() - 135.0).abs() < 1e-9);
+        }
+        recs => panic!("Unexpected records {:?}", recs),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn pressure() -> Result<(), Box<dyn std::error::Error>> {
+    let mut body = vec![0u8; 76];
+    body[0..4].copy_from_slice(&1_600_000_000i32.to_le_bytes());
+    // The pressure and depth are valid
+    body[24..28].copy_from_slice(&0x21i32.to_le_bytes());
+    body[12..16].copy_from_slice(&14_700i32.to_le_bytes());
+    body[36..40].copy_from_slice(&25_250i32.to_le_bytes());
+
+    match SonarDataRecord::<f32>::from(read_message(jsf_message(2060, &body))?) {
+        SonarDataRecord::Position(position) => {
+            assert_eq!(None, position.latitude);
+            assert_eq!(Some(-25.25), position.altitude);
+        }
+        rec => panic!("Unexpected record {:?}", rec),
+    }
+
+    // Cable counter and kilometer of pipe messages are parsed but have no record
+    let cable = read_message(jsf_message(2100, &[0u8; 32]))?;
+    assert!(matches!(
+        SonarDataRecord::<f32>::from(cable),
+        SonarDataRecord::Unknown
+    ));
+    let kp = read_message(jsf_message(2101, &[0u8; 20]))?;
+    assert!(matches!(
+        SonarDataRecord::<f32>::from(kp),
+        SonarDataRecord::Unknown
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn undersized_messages() -> Result<(), Box<dyn std::error::Error>> {
+    for (message_type, size) in [
+        (2060, 40),
+        (2080, 58),
+        (2090, 244),
+        (2091, 132),
+        (2100, 32),
+        (2101, 18),
+    ] {
+        let mut msg = jsf_message(message_type, &vec![0u8; size]);
+        read_message(msg.clone())?;
+        set_message_size(&mut msg, size as i32 - 1);
+        assert!(read_message(msg).is_err(), "message type {}", message_type);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn bathymetry() -> Result<(), Box<dyn std::error::Error>> {
+    let mut body = vec![0u8; 80 + 2 * 8];
+    body[0..4].copy_from_slice(&1_600_000_000i32.to_le_bytes());
+    body[4..8].copy_from_slice(&500_000_000u32.to_le_bytes());
+    body[12..14].copy_from_slice(&2u16.to_le_bytes());
+    body[40..44].copy_from_slice(&80u32.to_le_bytes());
+    body[48..52].copy_from_slice(&1e-5f32.to_le_bytes());
+    body[56..60].copy_from_slice(&0.5f32.to_le_bytes());
+    body[80..82].copy_from_slice(&2000u16.to_le_bytes());
+    body[82..84].copy_from_slice(&(-90i16).to_le_bytes());
+    body[84] = 200;
+    body[88..90].copy_from_slice(&4000u16.to_le_bytes());
+    body[90..92].copy_from_slice(&60i16.to_le_bytes());
+    body[92] = 100;
+
+    match SonarDataRecord::<f32>::from(read_message(jsf_message(3000, &body))?) {
+        SonarDataRecord::Bathymetry(bathymetry) => {
+            assert_eq!(
+                OffsetDateTime::from_unix_timestamp(1_600_000_000)? + Duration::milliseconds(500),
+                bathymetry.timestamp
+            );
+            assert_eq!(2, bathymetry.soundings.len());
+            let sounding = &bathymetry.soundings[0];
+            assert!((sounding.travel_time.unwrap() - 0.02).abs() < 1e-6);
+            assert_eq!(Some(-45.0), sounding.angle);
+            assert_eq!(Some(200.0), sounding.amplitude);
+            assert_eq!(None, sounding.depth);
+            assert!((bathymetry.soundings[1].travel_time.unwrap() - 0.04).abs() < 1e-6);
+            assert_eq!(Some(30.0), bathymetry.soundings[1].angle);
+        }
+        rec => panic!("Unexpected record {:?}", rec),
+    }
+
+    // The samples may start after a gap
+    let mut gap = body[..80].to_vec();
+    gap[40..44].copy_from_slice(&88u32.to_le_bytes());
+    gap.extend_from_slice(&[0xff; 8]);
+    gap.extend_from_slice(&body[80..]);
+    match SonarDataRecord::<f32>::from(read_message(jsf_message(3000, &gap))?) {
+        SonarDataRecord::Bathymetry(bathymetry) => {
+            assert_eq!(2, bathymetry.soundings.len());
+            assert_eq!(Some(200.0), bathymetry.soundings[0].amplitude);
+            assert_eq!(Some(100.0), bathymetry.soundings[1].amplitude);
+        }
+        rec => panic!("Unexpected record {:?}", rec),
+    }
+
+    // The samples must fit between the header and the end of the message
+    let mut bad = body.clone();
+    bad[12..14].copy_from_slice(&3u16.to_le_bytes());
+    assert!(read_message(jsf_message(3000, &bad)).is_err());
+    let mut bad = body.clone();
+    bad[40..44].copy_from_slice(&72u32.to_le_bytes());
+    assert!(read_message(jsf_message(3000, &bad)).is_err());
+    let mut bad = body.clone();
+    bad[40..44].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(read_message(jsf_message(3000, &bad)).is_err());
+    for (message_type, size) in [(3001, 32), (3002, 36), (3004, 32)] {
+        let mut msg = jsf_message(message_type, &vec![0u8; size]);
+        set_message_size(&mut msg, size as i32 - 1);
+        assert!(read_message(msg).is_err(), "message type {}", message_type);
+    }
+
+    let mut body = vec![0u8; 32];
+    body[0..4].copy_from_slice(&1_600_000_000i32.to_le_bytes());
+    // The heading and roll are valid
+    body[8..12].copy_from_slice(&0x9u32.to_le_bytes());
+    body[12..16].copy_from_slice(&45.0f32.to_le_bytes());
+    body[20..24].copy_from_slice(&3.0f32.to_le_bytes());
+    body[24..28].copy_from_slice(&(-1.5f32).to_le_bytes());
+    match SonarDataRecord::<f32>::from(read_message(jsf_message(3001, &body))?) {
+        SonarDataRecord::Orientation(orientation) => {
+            assert_eq!(Some(45.0), orientation.heading);
+            assert_eq!(None, orientation.pitch);
+            assert_eq!(Some(-1.5), orientation.roll);
+        }
+        rec => panic!("Unexpected record {:?}", rec),
+    }
+
+    let mut body = vec![0u8; 36];
+    body[0..4].copy_from_slice(&1_600_000_000i32.to_le_bytes());
+    // The sound speed and depth are valid
+    body[8..12].copy_from_slice(&0x30u32.to_le_bytes());
+    body[28..32].copy_from_slice(&1500.0f32.to_le_bytes());
+    body[32..36].copy_from_slice(&8.5f32.to_le_bytes());
+    match SonarDataRecord::<f32>::from(read_message(jsf_message(3002, &body))?) {
+        SonarDataRecord::Position(position) => {
+            assert_eq!(None, position.longitude);
+            assert_eq!(Some(-8.5), position.altitude);
+        }
+        rec => panic!("Unexpected record {:?}", rec),
+    }
+
+    let mut body = vec![0u8; 32];
+    body[0..4].copy_from_slice(&1_600_000_000i32.to_le_bytes());
+    body[8..12].copy_from_slice(&0x1u32.to_le_bytes());
+    body[12..20].copy_from_slice(&30.25f64.to_le_bytes());
+    body[20..28].copy_from_slice(&(-45.5f64).to_le_bytes());
+    match SonarDataRecord::<f32>::from(read_message(jsf_message(3004, &body))?) {
+        SonarDataRecord::Position(position) => {
+            assert_eq!(Some(30.25), position.latitude);
+            assert_eq!(Some(-45.5), position.longitude);
+            assert_eq!(None, position.altitude);
+        }
+        rec => panic!("Unexpected record {:?}", rec),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn system_messages() -> Result<(), Box<dyn std::error::Error>> {
+    let mut body = vec![0u8; 24 + 8];
+    body[0..4].copy_from_slice(&128i32.to_le_bytes());
+    body[8..12].copy_from_slice(&7i32.to_le_bytes());
+    body[12..16].copy_from_slice(&2i32.to_le_bytes());
+    body[20..24].copy_from_slice(&4321i32.to_le_bytes());
+    let msg = read_message(jsf_message(182, &body))?;
+    assert_eq!(182, msg.message_type());
+    assert_eq!(32, msg.message_size());
+    assert!(msg.navigation_offsets().is_none());
+    let info = msg.system_information().unwrap();
+    assert_eq!(128, info.system_type());
+    assert_eq!(7, info.version_number());
+    assert_eq!(2, info.n_subsystems());
+    assert_eq!(4321, info.serial_number());
+
+    let mut body = vec![0u8; 64];
+    body[16..20].copy_from_slice(&1.5f32.to_le_bytes());
+    body[20..24].copy_from_slice(&(-0.25f32).to_le_bytes());
+    body[24..28].copy_from_slice(&2.0f32.to_le_bytes());
+    body[36..40].copy_from_slice(&0.5f32.to_le_bytes());
+    let msg = read_message(jsf_message(181, &body))?;
+    assert!(msg.system_information().is_none());
+    let offsets = msg.navigation_offsets().unwrap();
+    assert_eq!(1.5, offsets.aft());
+    assert_eq!(-0.25, offsets.starboard());
+    assert_eq!(2.0, offsets.depth());
+    assert_eq!(0.5, offsets.pitch());
+
+    let offsets = sdw::model::NavigationOffsets::from(offsets);
+    assert_eq!(-1.5, offsets.forward);
+    assert_eq!(-0.25, offsets.starboard);
+    assert_eq!(2.0, offsets.down);
+    assert_eq!(0.5, offsets.pitch);
+
+    Ok(())
+}
+
+/// Build a JSF sonar data message (type 80) with the given time fields
+fn jsf_timed_ping(time: i32, milliseconds_today: u32, calendar: [i16; 6]) -> Vec<u8> {
+    let mut data = ping_body(time, milliseconds_today, &[]);
+    // Year, day, hour, minute, second and time basis
+    for (i, field) in calendar.iter().enumerate() {
+        data[156 + 2 * i..158 + 2 * i].copy_from_slice(&field.to_le_bytes());
+    }
+    jsf_ping(0, &data)
+}
+
+fn ping_timestamp(msg: Vec<u8>) -> Result<Option<OffsetDateTime>, Box<dyn std::error::Error>> {
+    match SonarDataRecord::<f32>::from(read_message(msg)?) {
+        SonarDataRecord::Ping(ping) => Ok(Some(ping.timestamp)),
+        SonarDataRecord::Unknown => Ok(None),
+        rec => panic!("Unexpected record {:?}", rec),
+    }
+}
+
+#[test]
+fn timestamps() -> Result<(), Box<dyn std::error::Error>> {
+    // 2020-09-13 12:26:40 UTC is day 257
+    let time = 1_600_000_000;
+    let expected =
+        OffsetDateTime::from_unix_timestamp(1_600_000_000)? + Duration::milliseconds(250);
+    let ms_today = 44_800_250;
+
+    // Without calendar fields only the milliseconds are used
+    assert_eq!(
+        Some(expected),
+        ping_timestamp(jsf_timed_ping(time, 250, [0; 6]))?
+    );
+    // Consistent calendar fields in UTC
+    let utc = [2020, 257, 12, 26, 40, 0];
+    assert_eq!(
+        Some(expected),
+        ping_timestamp(jsf_timed_ping(time, ms_today, utc))?
+    );
+    // Local time two hours ahead of UTC
+    let local = [2020, 257, 14, 26, 40, 1];
+    assert_eq!(
+        Some(expected),
+        ping_timestamp(jsf_timed_ping(time, ms_today + 7_200_000, local))?
+    );
+    // Pings whose time sources disagree keep the time since the epoch,
+    // and are reported by the file
+    let wrong_basis = [2020, 257, 14, 26, 40, 0];
+    let invalid = [2020, 400, 12, 26, 40, 0];
+    let pings = [
+        // The same local time claimed to be UTC
+        jsf_timed_ping(time, ms_today + 7_200_000, wrong_basis),
+        // The milliseconds today disagree with the calendar
+        jsf_timed_ping(time, 250, utc),
+        // Invalid calendar fields and milliseconds
+        jsf_timed_ping(time, ms_today, invalid),
+        jsf_timed_ping(time, 86_400_250, [0; 6]),
+    ];
+    for ping in &pings {
+        assert_eq!(Some(expected), ping_timestamp(ping.clone())?);
+    }
+
+    let mut file = jsf::File::new(Cursor::new(pings.concat()));
+    assert_eq!(4, file.by_ref().count());
+    let errors: Vec<_> = file.invalid_timestamps().iter().collect();
+    assert_eq!(4, errors.len());
+    assert_eq!(256, errors[1].offset);
+    assert_eq!(20, errors[1].subsystem);
+    assert!(matches!(
+        errors[0].error,
+        jsf::TimestampError::CalendarMismatch { .. }
+    ));
+    assert!(matches!(
+        errors[1].error,
+        jsf::TimestampError::MillisecondsMismatch { .. }
+    ));
+    assert_eq!(jsf::TimestampError::InvalidCalendar, errors[2].error);
+    assert_eq!(
+        jsf::TimestampError::InvalidMilliseconds(86_400_250),
+        errors[3].error
+    );
+
+    Ok(())
+}
+
+#[test]
+fn discontinuities() -> Result<(), Box<dyn std::error::Error>> {
+    let pings = [
+        jsf_timed_ping(1_600_000_000, 0, [0; 6]),
+        jsf_timed_ping(1_600_000_001, 0, [0; 6]),
+        jsf_timed_ping(1_599_999_990, 0, [0; 6]),
+        jsf_timed_ping(1_600_000_100, 0, [0; 6]),
+        jsf_timed_ping(1_600_000_101, 0, [0; 6]),
+    ];
+    let mut file = jsf::File::new(Cursor::new(pings.concat()));
+    assert_eq!(5, file.by_ref().count());
+
+    let discontinuities = file.discontinuities();
+    assert_eq!(2, discontinuities.len());
+    assert_eq!(2 * 256, discontinuities[0].offset);
+    assert_eq!(20, discontinuities[0].subsystem);
+    assert_eq!(
+        OffsetDateTime::from_unix_timestamp(1_600_000_001)?,
+        discontinuities[0].previous
+    );
+    assert_eq!(
+        OffsetDateTime::from_unix_timestamp(1_599_999_990)?,
+        discontinuities[0].timestamp
+    );
+    assert_eq!(3 * 256, discontinuities[1].offset);
+
+    // A larger gap is allowed when configured
+    let mut file = jsf::File::new(Cursor::new(pings.concat()));
+    file.set_max_gap(Duration::minutes(5));
+    assert_eq!(5, file.by_ref().count());
+    assert_eq!(1, file.discontinuities().len());
+
+    Ok(())
+}
+
+#[test]
+fn write_file() -> Result<(), Box<dyn std::error::Error>> {
+    let original = std::fs::read("assets/HE501_Hydro3_025.001.jsf")?;
+    let messages = jsf::File::new(Cursor::new(&original)).collect::<Result<Vec<_>, _>>()?;
+
+    let mut writer = jsf::Writer::new(Cursor::new(Vec::new()));
+    for msg in &messages {
+        writer.write(msg)?;
+    }
+    let written = writer.into_inner().into_inner();
+    assert_eq!(original.len(), written.len());
+
+    // Messages that are not parsed are reproduced byte for byte
+    let rewritten = jsf::File::new(Cursor::new(&written)).collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(messages, rewritten);
+
+    Ok(())
+}
+
+#[test]
+fn round_trip() -> Result<(), Box<dyn std::error::Error>> {
+    let mut situation = vec![0u8; 260];
+    situation[0..4].copy_from_slice(&1_600_000_000i32.to_le_bytes());
+    situation[28..36].copy_from_slice(&30.25f64.to_le_bytes());
+    let mut bathymetry = vec![0u8; 80 + 8];
+    bathymetry[12..14].copy_from_slice(&1u16.to_le_bytes());
+    bathymetry[40..44].copy_from_slice(&80u32.to_le_bytes());
+    bathymetry[80..88].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+    let original = [
+        jsf_nmea(1_600_000_000, "$GPHDT,54.7,T*03\r\n"),
+        jsf_nav_ping(0),
+        jsf_ping_data(1, 2, &[3, 0, 252, 255, 255, 255, 0, 0]),
+        jsf_message(2090, &situation),
+        jsf_message(3000, &bathymetry),
+        jsf_message(9999, &[0xde, 0xad, 0xbe, 0xef]),
+    ]
+    .concat();
+
+    let mut writer = jsf::Writer::new(Cursor::new(Vec::new()));
+    let mut unknown = 0;
+    for msg in jsf::File::new(Cursor::new(&original)) {
+        let msg = msg?;
+        if msg.is_unknown() {
+            unknown += 1;
+        }
+        writer.write(&msg)?;
+    }
+    assert_eq!(1, unknown);
+    assert_eq!(original, writer.into_inner().into_inner());
+
+    Ok(())
+}
+
+#[test]
+fn round_trip_messages() -> Result<(), Box<dyn std::error::Error>> {
+    for (message_type, size) in [
+        (181, 64),
+        (182, 32),
+        (2020, 44),
+        (2060, 76),
+        (2080, 80),
+        (2090, 260),
+        (2091, 132),
+        (2100, 32),
+        (2101, 20),
+        (3000, 80),
+        (3001, 32),
+        (3002, 36),
+        (3004, 32),
+    ] {
+        let mut body = vec![0u8; size];
+        body[0..4].copy_from_slice(&1_600_000_000i32.to_le_bytes());
+        if message_type == 3000 {
+            body[40..44].copy_from_slice(&80u32.to_le_bytes());
+        }
+        let original = jsf_message(message_type, &body);
+
+        let msg = read_message(original.clone())?;
+        assert!(!msg.is_unknown(), "message type {}", message_type);
+        let mut writer = jsf::Writer::new(Cursor::new(Vec::new()));
+        writer.write(&msg)?;
+        assert_eq!(
+            original,
+            writer.into_inner().into_inner(),
+            "message type {}",
+            message_type
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn edit_navigation() -> Result<(), Box<dyn std::error::Error>> {
+    let mut pitch_roll = vec![0u8; 44];
+    pitch_roll[0..4].copy_from_slice(&1_600_000_000i32.to_le_bytes());
+    let original = [
+        jsf_nav_ping(0),
+        jsf_message(2020, &pitch_roll),
+        jsf_nmea(1_600_000_000, "$GPHDT,54.7,T*03\r\n"),
+    ]
+    .concat();
+
+    let mut writer = jsf::Writer::new(Cursor::new(Vec::new()));
+    for msg in jsf::File::new(Cursor::new(&original)) {
+        let mut msg = msg?;
+        if let Some(ping) = msg.sonar_data_mut() {
+            ping.set_coordinates(Some(jsf::Coordinates::Geographic {
+                longitude: -45.25,
+                latitude: 30.5,
+            }));
+            ping.set_depth(None);
+        }
+        if let Some(pitch_roll) = msg.pitch_roll_data_mut() {
+            pitch_roll.set_heading(Some(271.5));
+        }
+        msg.set_nmea_sentence(
+            "$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47\r\n",
+        );
+        writer.write(&msg)?;
+    }
+    let edited = writer.into_inner().into_inner();
+
+    // Only the position and validity flag of the ping are changed
+    let changed: Vec<usize> = (0..256).filter(|&i| original[i] != edited[i]).collect();
+    assert!(changed
+        .iter()
+        .all(|i| (16 + 30..16 + 32).contains(i) || (16 + 80..16 + 88).contains(i)));
+
+    let messages = jsf::File::new(Cursor::new(edited)).collect::<Result<Vec<_>, _>>()?;
+    let ping = messages[0].sonar_data().unwrap();
+    assert_eq!(
+        Some(jsf::Coordinates::Geographic {
+            longitude: -45.25,
+            latitude: 30.5
+        }),
+        ping.coordinates()
+    );
+    assert_eq!(None, ping.depth());
+    assert_eq!(Some(8.0), ping.altitude());
+    assert_eq!(
+        Some(271.5),
+        messages[1].pitch_roll_data().unwrap().heading()
+    );
+    assert_eq!(None, messages[1].pitch_roll_data().unwrap().pitch());
+    match SonarDataRecord::<f32>::from(messages.into_iter().nth(2).unwrap()) {
+        SonarDataRecord::Position(position) => {
+            assert!((position.latitude.unwrap() - 48.1173).abs() < 1e-9);
+        }
+        rec => panic!("Unexpected record {:?}", rec),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn recover_corrupt_file() -> Result<(), Box<dyn std::error::Error>> {
+    let original = std::fs::read("assets/HE501_Hydro3_025.001.jsf")?;
+    let offsets = message_offsets(&original);
+
+    // Break the marker of the third message, insert garbage before the
+    // tenth and cut off the last message
+    let mut corrupt = original.clone();
+    corrupt[offsets[2]] = 0;
+    corrupt.truncate(original.len() - 7);
+    corrupt.splice(offsets[9]..offsets[9], [0xff; 13]);
+
+    let mut file = jsf::File::new(Cursor::new(&corrupt));
+    file.set_recover(true);
+    let messages = file.by_ref().collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(905 - 2, messages.len());
+
+    let last = *offsets.last().unwrap() as u64 + 13;
+    assert_eq!(
+        vec![
+            offsets[2] as u64..offsets[3] as u64,
+            offsets[9] as u64..offsets[9] as u64 + 13,
+            last..corrupt.len() as u64,
+        ],
+        file.skipped()
+    );
+
+    // Without recovery the first error is returned
+    let mut file = jsf::File::new(Cursor::new(&corrupt));
+    assert!(file.nth(2).unwrap().is_err());
+
+    Ok(())
+}
+
+#[test]
+fn recover() -> Result<(), Box<dyn std::error::Error>> {
+    let mut bad = jsf_message(9999, &[1, 2, 3, 4]);
+    bad[1] = 0;
+    let truncated = jsf_nav_ping(1);
+    // Messages of a type that SDW does not parse are only found while
+    // scanning if the type has been read before
+    let parts = [
+        jsf_nmea(1_600_000_000, "$GPHDT,54.7,T*03\r\n"),
+        jsf_message(9999, &[9, 9, 9, 9]),
+        vec![0xff, 0x01, 0x00, 0x16, 0x42],
+        jsf_nav_ping(0),
+        bad,
+        jsf_message(9999, &[5, 6, 7, 8]),
+        truncated[..truncated.len() - 10].to_vec(),
+    ];
+    let offsets: Vec<u64> = parts
+        .iter()
+        .scan(0, |offset, part| {
+            let start = *offset;
+            *offset += part.len() as u64;
+            Some(start)
+        })
+        .collect();
+    let data = parts.concat();
+
+    let mut file = jsf::File::new(Cursor::new(&data));
+    file.set_recover(true);
+    let messages = file.by_ref().collect::<Result<Vec<_>, _>>()?;
+    let types: Vec<u16> = messages.iter().map(|msg| msg.message_type()).collect();
+    assert_eq!(vec![2002, 9999, 80, 9999], types);
+    assert_eq!(
+        vec![
+            offsets[2]..offsets[3],
+            offsets[4]..offsets[5],
+            offsets[6]..data.len() as u64
+        ],
+        file.skipped()
+    );
+
+    // A ping whose message size is too large swallows the next message
+    let mut corrupt = jsf_nav_ping(0);
+    set_message_size(&mut corrupt, 280);
+    let parts = [
+        jsf_nmea(1_600_000_000, "$GPHDT,54.7,T*03\r\n"),
+        corrupt,
+        jsf_nmea(1_600_000_001, "$GPHDT,54.8,T*0C\r\n"),
+        jsf_nav_ping(0),
+    ];
+    let start = parts[0].len() as u64;
+    let next = start + parts[1].len() as u64;
+    let mut file = jsf::File::new(Cursor::new(parts.concat()));
+    file.set_recover(true);
+    let messages = file.by_ref().collect::<Result<Vec<_>, _>>()?;
+    let types: Vec<u16> = messages.iter().map(|msg| msg.message_type()).collect();
+    assert_eq!(vec![2002, 2002, 80], types);
+    assert_eq!(vec![start..next], file.skipped());
+
+    // A marker in the trace of a ping is not the start of a message
+    let mut trace = vec![0u8; 64];
+    trace[10..12].copy_from_slice(&[0x01, 0x16]);
+    trace[30..36].copy_from_slice(&[0x01, 0x16, 0x00, 0x00, 0x50, 0x00]);
+    let parts = [
+        jsf_nmea(1_600_000_000, "$GPHDT,54.7,T*03\r\n"),
+        jsf_ping(0, &ping_body(1_600_000_000, 0, &trace)),
+        jsf_nmea(1_600_000_001, "$GPHDT,54.8,T*0C\r\n"),
+    ];
+    let mut file = jsf::File::new(Cursor::new(parts.concat()));
+    file.set_recover(true);
+    let messages = file.by_ref().collect::<Result<Vec<_>, _>>()?;
+    let types: Vec<u16> = messages.iter().map(|msg| msg.message_type()).collect();
+    assert_eq!(vec![2002, 80, 2002], types);
+    assert!(file.skipped().is_empty());
+
+    // Without recovery the garbage is an error
+    let mut file = jsf::File::new(Cursor::new(&data));
+    assert!(file.next().unwrap().is_ok());
+    assert!(file.next().unwrap().is_ok());
+    assert!(file.next().unwrap().is_err());
+
+    Ok(())
+}
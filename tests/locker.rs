@@ -0,0 +1,483 @@
+mod common;
+
+use common::{jsf_ping, locker_dir, ping_body};
+use sdw::locker::{Locker, OpenOptions, SkipReason};
+use sdw::model::{Channel, NavigationOffsets, RecordKind, SonarDataRecord};
+use std::io::Write;
+use std::path::PathBuf;
+use time::OffsetDateTime;
+
+/// Build a JSF sonar data message (type 80) with `samples` zero samples
+fn ping_message(channel: u8, time: i32, milliseconds_today: u32, samples: usize) -> Vec<u8> {
+    jsf_ping(
+        channel,
+        &ping_body(time, milliseconds_today, &vec![0; 2 * samples]),
+    )
+}
+
+#[test]
+fn saved_index() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = locker_dir("saved_index");
+    let mut f = std::fs::File::create(dir.join("line1.jsf"))?;
+    f.write_all(&ping_message(0, 1_600_000_000, 0, 16))?;
+    f.write_all(&ping_message(1, 1_600_000_000, 0, 16))?;
+    f.flush()?;
+
+    let locker = Locker::open(dir.clone())?;
+    assert_eq!(2, locker.iter().count());
+    assert!(dir.join(".sdw-index").is_file());
+
+    let reopened = Locker::open(dir.clone())?;
+    assert_eq!(locker.index(), reopened.index());
+
+    // Appending to the file invalidates its saved entries
+    f.write_all(&ping_message(0, 1_600_000_001, 1000, 16))?;
+    f.flush()?;
+    let locker = Locker::open(dir.clone())?;
+    assert_eq!(3, locker.iter().count());
+
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[test]
+fn concurrent_opens() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = locker_dir("concurrent_opens");
+    std::fs::write(dir.join("line1.jsf"), ping_message(0, 1_600_000_000, 0, 16))?;
+
+    // Lockers that save the index at the same time do not share a
+    // temporary file
+    let handles: Vec<_> = (0..8)
+        .map(|_| {
+            let dir = dir.clone();
+            std::thread::spawn(move || Locker::open(dir).map(|locker| locker.iter().count()))
+        })
+        .collect();
+    for handle in handles {
+        assert_eq!(1, handle.join().unwrap()?);
+    }
+
+    let names: Vec<_> = std::fs::read_dir(&dir)?
+        .map(|entry| entry.map(|entry| entry.file_name()))
+        .collect::<Result<_, _>>()?;
+    assert_eq!(2, names.len());
+    assert_eq!(1, Locker::open(dir.clone())?.iter().count());
+
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[test]
+fn stale_index_version() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = locker_dir("stale_index_version");
+    std::fs::write(dir.join("line1.jsf"), ping_message(0, 1_600_000_000, 0, 16))?;
+    Locker::open(dir.clone())?;
+
+    // Rewrite the saved index as an older version that found no records
+    // in the unchanged file
+    let mut index = std::fs::read(dir.join(".sdw-index"))?;
+    let version = u16::from_le_bytes([index[4], index[5]]);
+    index[4..6].copy_from_slice(&(version - 1).to_le_bytes());
+    let path_len = u32::from_le_bytes(index[11..15].try_into()?) as usize;
+    let n_entries = 15 + path_len + 21;
+    index[n_entries..n_entries + 4].copy_from_slice(&0u32.to_le_bytes());
+    index.truncate(n_entries + 4);
+    std::fs::write(dir.join(".sdw-index"), index)?;
+
+    let locker = Locker::open(dir.clone())?;
+    assert_eq!(1, locker.iter().count());
+
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[test]
+fn query() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = locker_dir("query");
+    let mut f = std::fs::File::create(dir.join("line1.jsf"))?;
+    for i in 0..10 {
+        f.write_all(&ping_message(0, 1_600_000_000 + i, 1000 * i as u32, 16))?;
+        f.write_all(&ping_message(1, 1_600_000_000 + i, 1000 * i as u32, 16))?;
+    }
+    f.flush()?;
+
+    let locker = Locker::open(dir.clone())?;
+    let start = OffsetDateTime::from_unix_timestamp(1_600_000_002)?;
+    let end = OffsetDateTime::from_unix_timestamp(1_600_000_005)?;
+
+    assert_eq!(20, locker.query().kind(RecordKind::Ping).keys().count());
+    assert_eq!(0, locker.query().kind(RecordKind::Course).keys().count());
+    assert_eq!(
+        3,
+        locker
+            .query()
+            .kind(RecordKind::Ping)
+            .start(start)
+            .end(end)
+            .channel(Channel::Starboard)
+            .keys()
+            .count()
+    );
+    assert_eq!(6, locker.query().start(start).end(end).keys().count());
+    assert_eq!(0, locker.query().start(end).end(start).keys().count());
+    assert_eq!(20, locker.query().file("line1.jsf").keys().count());
+    assert_eq!(0, locker.query().file("line2.jsf").keys().count());
+
+    for rec in locker
+        .query()
+        .kind(RecordKind::Ping)
+        .channel(Channel::Port)
+        .records()
+    {
+        match rec? {
+            SonarDataRecord::Ping(ping) => {
+                assert_eq!(Channel::Port, ping.channel);
+                assert_eq!(16, ping.data.len());
+            }
+            _ => panic!("Expected a ping"),
+        }
+    }
+
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[test]
+fn duplicate_timestamps() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = locker_dir("duplicate_timestamps");
+    let mut f = std::fs::File::create(dir.join("line1.jsf"))?;
+    f.write_all(&ping_message(0, 1_600_000_000, 0, 16))?;
+    f.write_all(&ping_message(0, 1_600_000_000, 0, 32))?;
+    f.write_all(&ping_message(1, 1_600_000_000, 0, 16))?;
+    f.flush()?;
+    std::fs::copy(dir.join("line1.jsf"), dir.join("line1-copy.jsf"))?;
+
+    let locker = Locker::open(dir.clone())?;
+    assert_eq!(6, locker.iter().count());
+
+    let collisions = locker.collisions();
+    assert_eq!(2, collisions.len());
+    assert_eq!(Channel::Port, collisions[0].channel);
+    assert_eq!(4, collisions[0].sources.len());
+    assert_eq!(Channel::Starboard, collisions[1].channel);
+    assert_eq!(2, collisions[1].sources.len());
+
+    // Sequence numbers are stable when the index is reloaded
+    let reopened = Locker::open(dir.clone())?;
+    assert_eq!(locker.index(), reopened.index());
+    assert_eq!(locker.collisions(), reopened.collisions());
+
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[test]
+fn subsystems() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = locker_dir("subsystems");
+    let low = ping_message(0, 1_600_000_000, 0, 16);
+    let mut high = low.clone();
+    // The subsystem number is the eighth byte of the message header
+    high[7] = 21;
+    std::fs::write(dir.join("line1.jsf"), [low, high].concat())?;
+
+    let locker = Locker::open(dir.clone())?;
+    assert_eq!(2, locker.iter().count());
+    assert!(locker.collisions().is_empty());
+
+    let high: Vec<_> = locker.query().subsystem(21).keys().collect();
+    assert_eq!(1, high.len());
+    assert_eq!(21, high[0].3);
+    match locker.get(high[0])? {
+        SonarDataRecord::Ping(ping) => assert_eq!(21, ping.subsystem),
+        rec => panic!("Unexpected record {:?}", rec),
+    }
+
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[test]
+fn indexing_errors() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = locker_dir("indexing_errors");
+    let ping = ping_message(0, 1_600_000_000, 0, 16);
+
+    let mut good = std::fs::File::create(dir.join("good.jsf"))?;
+    good.write_all(&ping)?;
+    good.flush()?;
+
+    let mut corrupt = std::fs::File::create(dir.join("corrupt.jsf"))?;
+    corrupt.write_all(&ping)?;
+    corrupt.write_all(&[0xff; 64])?;
+    corrupt.flush()?;
+
+    let mut truncated = std::fs::File::create(dir.join("truncated.jsf"))?;
+    truncated.write_all(&ping)?;
+    truncated.write_all(&ping[..100])?;
+    truncated.flush()?;
+
+    for _ in 0..2 {
+        let locker = Locker::open(dir.clone())?;
+        let report = locker.report();
+        assert!(!report.is_ok());
+        assert_eq!(3, report.records());
+        assert_eq!(3, report.files.len());
+
+        let errors: Vec<_> = report.errors().collect();
+        assert_eq!(2, errors.len());
+        for file in errors {
+            assert_eq!(1, file.records);
+            assert!(!file.cached);
+            assert_eq!(Some(ping.len() as u64), file.error.as_ref().map(|e| e.0));
+        }
+    }
+
+    // The good file is reloaded from the saved index
+    let locker = Locker::open(dir.clone())?;
+    assert!(locker.report().files.iter().any(|file| file.cached));
+
+    assert!(OpenOptions::new().strict(true).open(dir.clone()).is_err());
+
+    std::fs::remove_file(dir.join("corrupt.jsf"))?;
+    std::fs::remove_file(dir.join("truncated.jsf"))?;
+    let locker = OpenOptions::new().strict(true).open(dir.clone())?;
+    assert!(locker.report().is_ok());
+
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[test]
+fn recursive_patterns() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = locker_dir("recursive_patterns");
+    let ping = ping_message(0, 1_600_000_000, 0, 16);
+    std::fs::create_dir_all(dir.join("day1/line1"))?;
+    std::fs::create_dir_all(dir.join("day1/raw"))?;
+    std::fs::write(dir.join("day1/line1/a.jsf"), &ping)?;
+    std::fs::write(dir.join("day1/line1/b.dat"), &ping)?;
+    std::fs::write(dir.join("day1/raw/c.jsf"), &ping)?;
+    std::fs::write(dir.join("day1/line1/log.txt"), "Survey log")?;
+    std::fs::write(dir.join(".DS_Store"), [0u8; 8])?;
+
+    let locker = Locker::open(dir.clone())?;
+    assert_eq!(3, locker.iter().count());
+    let skipped = &locker.report().skipped;
+    assert_eq!(2, skipped.len());
+    assert!(skipped
+        .iter()
+        .all(|file| file.reason == SkipReason::UnknownFormat));
+
+    let locker = OpenOptions::new()
+        .include("*.jsf")
+        .exclude("day1/raw")
+        .open(dir.clone())?;
+    assert_eq!(1, locker.iter().count());
+    let reasons: Vec<_> = locker
+        .report()
+        .skipped
+        .iter()
+        .map(|file| {
+            (
+                file.path.strip_prefix(&dir).unwrap().to_owned(),
+                file.reason,
+            )
+        })
+        .collect();
+    assert_eq!(
+        vec![
+            (PathBuf::from(".DS_Store"), SkipReason::NotIncluded),
+            (PathBuf::from("day1/line1/b.dat"), SkipReason::NotIncluded),
+            (PathBuf::from("day1/line1/log.txt"), SkipReason::NotIncluded),
+            (PathBuf::from("day1/raw"), SkipReason::Excluded),
+        ],
+        reasons
+    );
+
+    let locker = OpenOptions::new().recursive(false).open(dir.clone())?;
+    assert_eq!(0, locker.iter().count());
+    assert_eq!(
+        SkipReason::Directory,
+        locker.report().skipped.last().unwrap().reason
+    );
+
+    assert!(OpenOptions::new().include("[").open(dir.clone()).is_err());
+
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[test]
+fn bounded_resources() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = locker_dir("bounded_resources");
+    for i in 0..8 {
+        std::fs::write(
+            dir.join(format!("line{}.jsf", i)),
+            ping_message(0, 1_600_000_000 + i, 0, 16),
+        )?;
+    }
+
+    let locker = OpenOptions::new()
+        .threads(3)
+        .open_files(2)
+        .open(dir.clone())?;
+    assert_eq!(8, locker.report().files.len());
+
+    // Reading from more files than the handle limit reopens files as needed
+    for _ in 0..2 {
+        let records = locker.query().records().collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(8, records.len());
+    }
+
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[test]
+fn concurrent_reads() -> Result<(), Box<dyn std::error::Error>> {
+    fn assert_sync<T: Send + Sync>() {}
+    assert_sync::<Locker>();
+
+    let dir = locker_dir("concurrent_reads");
+    let mut f = std::fs::File::create(dir.join("line1.jsf"))?;
+    for i in 0..50 {
+        f.write_all(&ping_message(0, 1_600_000_000 + i, 0, 16 + i as usize))?;
+    }
+    f.flush()?;
+
+    let locker = OpenOptions::new().open_files(1).open(dir.clone())?;
+    let keys: Vec<_> = locker.query().keys().collect();
+
+    std::thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                for (i, key) in keys.iter().enumerate() {
+                    match locker.get(key).unwrap() {
+                        SonarDataRecord::Ping(ping) => assert_eq!(16 + i, ping.data.len()),
+                        _ => panic!("Expected a ping"),
+                    }
+                }
+            });
+        }
+    });
+
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[test]
+fn refresh() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = locker_dir("refresh");
+    let mut f = std::fs::File::create(dir.join("line1.jsf"))?;
+    f.write_all(&ping_message(0, 1_600_000_000, 0, 16))?;
+    f.flush()?;
+
+    let mut locker = Locker::open(dir.clone())?;
+    assert_eq!(1, locker.iter().count());
+    assert!(locker.refresh()?.is_empty());
+
+    // A message that is still being written is not an error
+    let ping = ping_message(1, 1_600_000_001, 1000, 16);
+    f.write_all(&ping_message(0, 1_600_000_001, 1000, 16))?;
+    f.write_all(&ping[..100])?;
+    f.flush()?;
+    let keys = locker.refresh()?;
+    assert_eq!(1, keys.len());
+    assert_eq!(Channel::Port, keys[0].2);
+    assert!(locker.report().is_ok());
+
+    // The rest of the message and a new file are picked up
+    f.write_all(&ping[100..])?;
+    f.flush()?;
+    std::fs::write(
+        dir.join("line2.jsf"),
+        ping_message(0, 1_600_000_002, 2000, 16),
+    )?;
+    let keys = locker.refresh()?;
+    assert_eq!(2, keys.len());
+    for key in &keys {
+        assert!(matches!(locker.get(key)?, SonarDataRecord::Ping(_)));
+    }
+    assert_eq!(4, locker.iter().count());
+    assert_eq!(4, locker.report().records());
+
+    // Removed files are dropped from the index
+    std::fs::remove_file(dir.join("line2.jsf"))?;
+    assert!(locker.refresh()?.is_empty());
+    assert_eq!(3, locker.iter().count());
+    assert_eq!(1, locker.report().files.len());
+
+    // A file that is rewritten without growing is indexed afresh
+    let rewritten: Vec<u8> = (0..3)
+        .flat_map(|i| ping_message(1, 1_600_000_010 + i, 1000 * i as u32, 16))
+        .collect();
+    assert_eq!(f.metadata()?.len(), rewritten.len() as u64);
+    std::fs::write(dir.join("line1.jsf"), rewritten)?;
+    std::fs::File::options()
+        .write(true)
+        .open(dir.join("line1.jsf"))?
+        .set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(60))?;
+    let keys = locker.refresh()?;
+    assert_eq!(3, keys.len());
+    assert!(keys.iter().all(|key| key.2 == Channel::Starboard));
+    assert_eq!(3, locker.iter().count());
+
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[test]
+fn companion_records() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = locker_dir("companion_records");
+    let mut ping = ping_message(0, 1_600_000_000, 0, 16);
+    // Flag the heading as valid
+    ping[16 + 30..16 + 32].copy_from_slice(&0x0008u16.to_le_bytes());
+    ping[16 + 172..16 + 174].copy_from_slice(&9_000u16.to_le_bytes());
+    std::fs::write(dir.join("line1.jsf"), &ping)?;
+
+    let locker = Locker::open(dir.clone())?;
+    assert_eq!(1, locker.iter().count());
+
+    // The saved index is not reused with different options
+    let locker = OpenOptions::new()
+        .companion_records(true)
+        .open(dir.clone())?;
+    assert_eq!(2, locker.iter().count());
+    let orientation: Vec<_> = locker
+        .query()
+        .kind(RecordKind::Orientation)
+        .records()
+        .collect::<Result<_, _>>()?;
+    match &orientation[..] {
+        [SonarDataRecord::Orientation(orientation)] => {
+            assert_eq!(Some(90.0), orientation.heading)
+        }
+        recs => panic!("Unexpected records {:?}", recs),
+    }
+    let ping = locker.query().kind(RecordKind::Ping).records().next();
+    assert!(matches!(ping, Some(Ok(SonarDataRecord::Ping(_)))));
+
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[test]
+fn navigation_offsets() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = locker_dir("navigation_offsets");
+    std::fs::write(dir.join("a.jsf"), ping_message(0, 1_600_000_000, 0, 4))?;
+
+    let offsets = NavigationOffsets {
+        forward: -3.5,
+        starboard: 0.25,
+        ..Default::default()
+    };
+    let mut locker = OpenOptions::new()
+        .navigation_offsets(offsets)
+        .open(dir.clone())?;
+    assert_eq!(Some(&offsets), locker.navigation_offsets());
+
+    locker.set_navigation_offsets(None);
+    assert_eq!(None, locker.navigation_offsets());
+    assert_eq!(None, Locker::open(dir.clone())?.navigation_offsets());
+
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
+}
@@ -1,5 +1,12 @@
+mod common;
+
 use binrw::io::BufReader;
+use common::locker_dir;
+use sdw::locker::OpenOptions;
+use sdw::model::{Channel, RecordKind, SonarDataRecord};
 use sdw::parser::xtf;
+use std::io::Cursor;
+use time::{Duration, OffsetDateTime};
 
 #[test]
 fn read_file() -> Result<(), Box<dyn std::error::Error>> {
@@ -11,3 +18,578 @@ fn read_file() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+/// A channel of an XTF file header: type of channel, bytes per sample and frequency in kHz
+type ChanInfo = (u8, u16, f32);
+
+/// Build an XTF file header with the given navigation units and channels
+fn xtf_header(nav_units: u16, chaninfos: &[ChanInfo]) -> Vec<u8> {
+    let blocks = 1 + chaninfos.len().saturating_sub(6).div_ceil(8);
+    let mut header = vec![0u8; 1024 * blocks];
+    header[0] = 0x7b;
+    header[1] = 1;
+    header[164..166].copy_from_slice(&nav_units.to_le_bytes());
+    header[166..168].copy_from_slice(&(chaninfos.len() as u16).to_le_bytes());
+    for (i, (type_of_channel, bytes_per_sample, frequency)) in chaninfos.iter().enumerate() {
+        let info = &mut header[256 + 128 * i..256 + 128 * (i + 1)];
+        info[0] = *type_of_channel;
+        info[6..8].copy_from_slice(&bytes_per_sample.to_le_bytes());
+        info[32..36].copy_from_slice(&frequency.to_le_bytes());
+    }
+    header
+}
+
+/// Build an XTF sonar packet with 16 bit samples in each channel
+///
+/// The ping was acquired at 2020-09-13 12:26:40.25 by a sensor at 45.5
+/// degrees west and 30.25 degrees north.
+fn xtf_ping(channels: &[(u16, u16, f32, &[u16])]) -> Vec<u8> {
+    let channels: Vec<(u16, u16, f32, u32, Vec<u8>)> = channels
+        .iter()
+        .map(|(channel_number, frequency, slant_range, samples)| {
+            let bytes = samples.iter().flat_map(|x| x.to_le_bytes()).collect();
+            (
+                *channel_number,
+                *frequency,
+                *slant_range,
+                samples.len() as u32,
+                bytes,
+            )
+        })
+        .collect();
+    xtf_raw_ping(&channels)
+}
+
+/// Build an XTF sonar packet with channel number, frequency, slant range,
+/// number of samples and raw sample bytes for each channel
+fn xtf_raw_ping(channels: &[(u16, u16, f32, u32, Vec<u8>)]) -> Vec<u8> {
+    let mut ping = vec![0u8; 256];
+    ping[0..2].copy_from_slice(&0xfaceu16.to_le_bytes());
+    ping[4..6].copy_from_slice(&(channels.len() as u16).to_le_bytes());
+    ping[14..16].copy_from_slice(&2020u16.to_le_bytes());
+    ping[16..22].copy_from_slice(&[9, 13, 12, 26, 40, 25]);
+    ping[152..156].copy_from_slice(&4.0f32.to_le_bytes());
+    ping[160..168].copy_from_slice(&30.25f64.to_le_bytes());
+    ping[168..176].copy_from_slice(&(-45.5f64).to_le_bytes());
+    ping[192..196].copy_from_slice(&12.5f32.to_le_bytes());
+    ping[204..208].copy_from_slice(&1.5f32.to_le_bytes());
+    ping[208..212].copy_from_slice(&(-2.0f32).to_le_bytes());
+    ping[212..216].copy_from_slice(&90.0f32.to_le_bytes());
+
+    for (channel_number, frequency, slant_range, num_samples, samples) in channels {
+        let mut chan = vec![0u8; 64];
+        chan[0..2].copy_from_slice(&channel_number.to_le_bytes());
+        chan[4..8].copy_from_slice(&slant_range.to_le_bytes());
+        chan[26..28].copy_from_slice(&frequency.to_le_bytes());
+        chan[42..46].copy_from_slice(&num_samples.to_le_bytes());
+        ping.extend_from_slice(&chan);
+        ping.extend_from_slice(samples);
+    }
+    let size = ping.len() as u32;
+    ping[10..14].copy_from_slice(&size.to_le_bytes());
+    ping
+}
+
+#[test]
+fn records() -> Result<(), Box<dyn std::error::Error>> {
+    // A dual frequency side scan with port and starboard channels
+    let chaninfos = [(1, 2, 100.0), (2, 2, 100.0), (1, 2, 400.0), (2, 2, 400.0)];
+    let data = [
+        xtf_header(3, &chaninfos),
+        xtf_ping(&[(0, 100, 75.0, &[1, 2, 3]), (1, 100, 75.0, &[4, 5, 6])]),
+        xtf_ping(&[(2, 0, 30.0, &[7, 8]), (3, 0, 30.0, &[9, 10])]),
+    ]
+    .concat();
+
+    let mut file = xtf::File::new(Cursor::new(data));
+    let timestamp =
+        OffsetDateTime::from_unix_timestamp(1_600_000_000)? + Duration::milliseconds(250);
+
+    let packet = file.next().unwrap()?;
+    let records = packet.records(file.header());
+    assert_eq!(2, records.len());
+    match &records[0] {
+        SonarDataRecord::Ping(ping) => {
+            assert_eq!(timestamp, ping.timestamp);
+            assert_eq!(Channel::Port, ping.channel);
+            assert_eq!(0, ping.subsystem);
+            assert_eq!(100_000.0, ping.frequency);
+            assert!((ping.sampling_interval - 0.1 / 3.0).abs() < 1e-12);
+            assert_eq!(vec![1.0, 2.0, 3.0], ping.data);
+        }
+        rec => panic!("Unexpected record {:?}", rec),
+    }
+    match &records[1] {
+        SonarDataRecord::Ping(ping) => assert_eq!(Channel::Starboard, ping.channel),
+        rec => panic!("Unexpected record {:?}", rec),
+    }
+
+    let companions: Vec<SonarDataRecord<f32>> = packet.companion_records(file.header());
+    assert_eq!(3, companions.len());
+    match &companions[0] {
+        SonarDataRecord::Position(position) => {
+            assert_eq!(timestamp, position.timestamp);
+            assert_eq!(Some(-45.5), position.longitude);
+            assert_eq!(Some(30.25), position.latitude);
+            assert_eq!(Some(-12.5), position.altitude);
+        }
+        rec => panic!("Unexpected record {:?}", rec),
+    }
+    match &companions[1] {
+        SonarDataRecord::Orientation(orientation) => {
+            assert_eq!(Some(1.5), orientation.pitch);
+            assert_eq!(Some(-2.0), orientation.roll);
+            assert_eq!(Some(90.0), orientation.heading);
+        }
+        rec => panic!("Unexpected record {:?}", rec),
+    }
+    match &companions[2] {
+        SonarDataRecord::Course(course) => {
+            assert!((course.speed.unwrap() - 4.0 * 1852.0 / 3600.0).abs() < 1e-9);
+            assert_eq!(None, course.heading);
+        }
+        rec => panic!("Unexpected record {:?}", rec),
+    }
+
+    // The high frequency channels are a second subsystem, and take their
+    // frequency from the file header
+    let packet = file.next().unwrap()?;
+    match &packet.records(file.header())[..] {
+        [SonarDataRecord::Ping(port), SonarDataRecord::Ping(starboard)] => {
+            assert_eq!((Channel::Port, 1), (port.channel, port.subsystem));
+            assert_eq!(
+                (Channel::Starboard, 1),
+                (starboard.channel, starboard.subsystem)
+            );
+            assert_eq!(400_000.0, port.frequency);
+        }
+        recs => panic!("Unexpected records {:?}", recs),
+    }
+    assert!(file.next().is_none());
+
+    Ok(())
+}
+
+#[test]
+fn locker() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = locker_dir("xtf_locker");
+    let data = [
+        xtf_header(3, &[(1, 2, 100.0), (2, 2, 100.0)]),
+        xtf_ping(&[(0, 100, 75.0, &[1, 2, 3]), (1, 100, 75.0, &[4, 5, 6])]),
+    ]
+    .concat();
+    std::fs::write(dir.join("line.xtf"), data)?;
+
+    let locker = OpenOptions::new()
+        .companion_records(true)
+        .open(dir.clone())?;
+    assert_eq!(5, locker.iter().count());
+
+    // Both pings are read from the same packet
+    let pings: Vec<_> = locker.query().kind(RecordKind::Ping).records().collect();
+    match &pings[..] {
+        [Ok(SonarDataRecord::Ping(port)), Ok(SonarDataRecord::Ping(starboard))] => {
+            assert_eq!(vec![1.0, 2.0, 3.0], port.data);
+            assert_eq!(vec![4.0, 5.0, 6.0], starboard.data);
+        }
+        recs => panic!("Unexpected records {:?}", recs),
+    }
+
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[test]
+fn sample_formats() -> Result<(), Box<dyn std::error::Error>> {
+    // Bytes per sample, sample format, unipolar, volt scale, raw samples
+    // and the expected samples for each channel format
+    let cases: [(u16, u8, u16, f32, Vec<u8>, Vec<f32>); 9] = [
+        (1, 0, 1, 0.0, vec![0, 255], vec![0.0, 255.0]),
+        (1, 8, 0, 0.0, vec![0x80, 0x7f], vec![-128.0, 127.0]),
+        (
+            2,
+            0,
+            1,
+            0.0,
+            [1u16, 65535].iter().flat_map(|x| x.to_le_bytes()).collect(),
+            vec![1.0, 65535.0],
+        ),
+        (
+            2,
+            3,
+            0,
+            0.0,
+            [-2i16, 3].iter().flat_map(|x| x.to_le_bytes()).collect(),
+            vec![-2.0, 3.0],
+        ),
+        (
+            4,
+            0,
+            1,
+            0.0,
+            [7u32, 1 << 24]
+                .iter()
+                .flat_map(|x| x.to_le_bytes())
+                .collect(),
+            vec![7.0, 16_777_216.0],
+        ),
+        (
+            2,
+            2,
+            0,
+            0.0,
+            [-7i32, 9].iter().flat_map(|x| x.to_le_bytes()).collect(),
+            vec![-7.0, 9.0],
+        ),
+        (
+            4,
+            5,
+            0,
+            0.0,
+            [0.5f32, -1.25]
+                .iter()
+                .flat_map(|x| x.to_le_bytes())
+                .collect(),
+            vec![0.5, -1.25],
+        ),
+        // IBM floating point 1.0 and -118.625
+        (
+            4,
+            1,
+            0,
+            0.0,
+            [0x4110_0000u32, 0xc276_a000]
+                .iter()
+                .flat_map(|x| x.to_le_bytes())
+                .collect(),
+            vec![1.0, -118.625],
+        ),
+        // The maximum sample value is the volt scale
+        (
+            2,
+            0,
+            1,
+            5.0,
+            [0u16, 65535].iter().flat_map(|x| x.to_le_bytes()).collect(),
+            vec![0.0, 5.0],
+        ),
+    ];
+
+    for (bytes_per_sample, sample_format, unipolar, volt_scale, raw, expected) in cases {
+        let mut header = xtf_header(3, &[(1, bytes_per_sample, 100.0)]);
+        header[256 + 4..256 + 6].copy_from_slice(&unipolar.to_le_bytes());
+        header[256 + 28..256 + 32].copy_from_slice(&volt_scale.to_le_bytes());
+        header[256 + 74] = sample_format;
+        let data = [header, xtf_raw_ping(&[(0, 100, 75.0, 2, raw)])].concat();
+
+        let mut file = xtf::File::new(Cursor::new(data));
+        let packet = file.next().unwrap()?;
+        match &packet.records(file.header())[..] {
+            [SonarDataRecord::Ping(ping)] => assert_eq!(
+                expected, ping.data,
+                "bytes per sample {}, sample format {}",
+                bytes_per_sample, sample_format
+            ),
+            recs => panic!("Unexpected records {:?}", recs),
+        }
+        assert!(file.next().is_none());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn extended_chaninfos() -> Result<(), Box<dyn std::error::Error>> {
+    // Four sonar channels and five bathymetry channels need a second
+    // block of ChanInfo entries
+    let mut chaninfos = vec![(1, 2, 100.0), (2, 2, 100.0), (1, 2, 400.0), (2, 2, 400.0)];
+    chaninfos.extend([(3, 2, 200.0); 5]);
+    chaninfos.push((1, 2, 900.0));
+    let mut header = xtf_header(3, &chaninfos);
+    // Count all but the last channel as sonar channels and the rest as
+    // bathymetry channels
+    header[166..168].copy_from_slice(&4u16.to_le_bytes());
+    header[168..170].copy_from_slice(&6u16.to_le_bytes());
+    assert_eq!(2048, header.len());
+    let data = [header, xtf_ping(&[(9, 0, 30.0, &[1, 2])])].concat();
+
+    let mut file = xtf::File::new(Cursor::new(data));
+    assert_eq!(10, file.header().number_of_channels());
+    assert_eq!(14, file.header().chaninfos().len());
+    assert_eq!(900_000.0, file.header().chaninfos()[9].frequency());
+
+    let packet = file.next().unwrap()?;
+    match &packet.records(file.header())[..] {
+        [SonarDataRecord::Ping(ping)] => {
+            assert_eq!((Channel::Port, 2), (ping.channel, ping.subsystem));
+            assert_eq!(900_000.0, ping.frequency);
+            assert_eq!(vec![1.0, 2.0], ping.data);
+        }
+        recs => panic!("Unexpected records {:?}", recs),
+    }
+    assert!(file.next().is_none());
+
+    Ok(())
+}
+
+/// Build an XTF packet of the given header type with the given body
+fn xtf_packet(header_type: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![0u8; 14];
+    packet[0..2].copy_from_slice(&0xfaceu16.to_le_bytes());
+    packet[2] = header_type;
+    packet.extend_from_slice(body);
+    packet.resize(packet.len().max(64), 0);
+    let size = packet.len() as u32;
+    packet[10..14].copy_from_slice(&size.to_le_bytes());
+    packet
+}
+
+#[test]
+fn navigation_packets() -> Result<(), Box<dyn std::error::Error>> {
+    let date = [&2020u16.to_le_bytes()[..], &[9, 13, 12, 26, 40]].concat();
+    let timestamp = OffsetDateTime::from_unix_timestamp(1_600_000_000)?;
+
+    let mut notes = date.clone();
+    notes.resize(42, 0);
+    notes.extend_from_slice(b"Start of line\0");
+    notes.resize(242, 0);
+
+    let mut attitude = vec![0u8; 8];
+    attitude.extend_from_slice(&0u32.to_le_bytes());
+    attitude.extend_from_slice(&1_600_000_000u32.to_le_bytes());
+    for value in [1.5f32, -2.0, 0.25, 91.0, 0.0, 90.0] {
+        attitude.extend_from_slice(&value.to_le_bytes());
+    }
+    // The date is not set, so the time is found from the source epoch
+    attitude[8..12].copy_from_slice(&7u32.to_le_bytes());
+
+    let mut raw_serial = date.clone();
+    raw_serial.push(50);
+    raw_serial.extend_from_slice(&[0; 6]);
+    raw_serial.extend_from_slice(&6u16.to_le_bytes());
+    raw_serial.extend_from_slice(b"$GPGGA");
+
+    let mut navigation = date.clone();
+    navigation.extend_from_slice(&250_000u32.to_le_bytes());
+    navigation.extend_from_slice(&[0; 8]);
+    for value in [30.25f64, -45.5, 3.0] {
+        navigation.extend_from_slice(&value.to_le_bytes());
+    }
+
+    let mut gyro = date.clone();
+    gyro.extend_from_slice(&[0; 12]);
+    gyro.extend_from_slice(&45.0f32.to_le_bytes());
+
+    let mut position = date.clone();
+    position.extend_from_slice(&5000u16.to_le_bytes());
+    for value in [30.25f64, -45.5, 3.0] {
+        position.extend_from_slice(&value.to_le_bytes());
+    }
+    for value in [1.5f32, -2.0, 0.25, 90.0] {
+        position.extend_from_slice(&value.to_le_bytes());
+    }
+
+    let data = [
+        xtf_header(3, &[(1, 2, 100.0)]),
+        xtf_packet(1, &notes),
+        xtf_packet(3, &attitude),
+        {
+            // The serial port is the sub-channel number
+            let mut packet = xtf_packet(6, &raw_serial);
+            packet[3] = 2;
+            packet
+        },
+        xtf_packet(42, &navigation),
+        xtf_packet(84, &gyro),
+        xtf_packet(107, &position),
+    ]
+    .concat();
+    let mut file = xtf::File::new(Cursor::new(data));
+    let packets = file.by_ref().collect::<Result<Vec<_>, _>>()?;
+    let names: Vec<String> = packets.iter().map(|p| p.packet_name()).collect();
+    assert_eq!(
+        vec![
+            "Notes",
+            "Attitude",
+            "RawSerial",
+            "Navigation",
+            "Gyro",
+            "Position"
+        ],
+        names
+    );
+
+    match packets[0].packet_type() {
+        xtf::PacketType::Notes(notes) => {
+            assert_eq!(Some(timestamp), notes.timestamp());
+            assert_eq!("Start of line", notes.notes_text());
+        }
+        packet => panic!("Unexpected packet {:?}", packet),
+    }
+    assert!(packets[0].records(file.header()).is_empty());
+    match packets[2].packet_type() {
+        xtf::PacketType::RawSerial(serial) => {
+            assert_eq!(2, packets[2].sub_channel_number());
+            assert_eq!(
+                Some(timestamp + Duration::milliseconds(500)),
+                serial.timestamp()
+            );
+            assert_eq!("$GPGGA", serial.raw_ascii_data());
+        }
+        packet => panic!("Unexpected packet {:?}", packet),
+    }
+
+    match &packets[1].records(file.header())[..] {
+        [SonarDataRecord::Orientation(orientation)] => {
+            assert_eq!(timestamp + Duration::microseconds(7), orientation.timestamp);
+            assert_eq!(Some(1.5), orientation.pitch);
+            assert_eq!(Some(-2.0), orientation.roll);
+            assert_eq!(Some(90.0), orientation.heading);
+        }
+        recs => panic!("Unexpected records {:?}", recs),
+    }
+    match &packets[3].records(file.header())[..] {
+        [SonarDataRecord::Position(position)] => {
+            assert_eq!(timestamp + Duration::milliseconds(250), position.timestamp);
+            assert_eq!(Some(-45.5), position.longitude);
+            assert_eq!(Some(30.25), position.latitude);
+            assert_eq!(Some(3.0), position.altitude);
+        }
+        recs => panic!("Unexpected records {:?}", recs),
+    }
+    match &packets[4].records(file.header())[..] {
+        [SonarDataRecord::Orientation(orientation)] => {
+            assert_eq!(None, orientation.pitch);
+            assert_eq!(Some(45.0), orientation.heading);
+        }
+        recs => panic!("Unexpected records {:?}", recs),
+    }
+    match &packets[5].records(file.header())[..] {
+        [SonarDataRecord::Position(position), SonarDataRecord::Orientation(orientation)] => {
+            assert_eq!(timestamp + Duration::milliseconds(500), position.timestamp);
+            assert_eq!(Some(-45.5), position.longitude);
+            assert_eq!(Some(90.0), orientation.heading);
+        }
+        recs => panic!("Unexpected records {:?}", recs),
+    }
+
+    Ok(())
+}
+
+/// Build an XTF bathymetry packet of the given header type with a ping
+/// header followed by the given data
+fn xtf_bathy(header_type: u8, data: &[u8]) -> Vec<u8> {
+    let mut packet = xtf_raw_ping(&[]);
+    packet[2] = header_type;
+    packet.extend_from_slice(data);
+    let size = packet.len() as u32;
+    packet[10..14].copy_from_slice(&size.to_le_bytes());
+    packet
+}
+
+#[test]
+fn bathymetry() -> Result<(), Box<dyn std::error::Error>> {
+    let mut entries = Vec::new();
+    for (id, across_track, depth) in [(0, -10.5, 20.0), (1, 11.0, 21.5)] {
+        let mut entry = Vec::new();
+        entry.extend_from_slice(&(id as i32).to_le_bytes());
+        entry.extend_from_slice(&(-30.0f64).to_le_bytes());
+        entry.extend_from_slice(&3i32.to_le_bytes());
+        for value in [0.03f64, 0.001, across_track, 0.5, depth] {
+            entry.extend_from_slice(&value.to_le_bytes());
+        }
+        entry.resize(72, 0);
+        entries.extend_from_slice(&entry);
+    }
+
+    let dir = locker_dir("xtf_bathymetry");
+    let data = [
+        xtf_header(3, &[(3, 2, 200.0)]),
+        xtf_bathy(2, &[1, 2, 3, 4, 5]),
+        xtf_bathy(60, &[1, 2, 3, 4, 5]),
+        xtf_bathy(28, &entries),
+    ]
+    .concat();
+    std::fs::write(dir.join("line.xtf"), &data)?;
+
+    let mut file = xtf::File::new(Cursor::new(data));
+    let packet = file.next().unwrap()?;
+    assert_eq!("Bathy", packet.packet_name());
+    match packet.packet_type() {
+        xtf::PacketType::Bathy(bathy) => assert_eq!(&[1, 2, 3, 4, 5], bathy.data()),
+        packet => panic!("Unexpected packet {:?}", packet),
+    }
+    assert!(packet.records(file.header()).is_empty());
+
+    // Types 60 and 61 are not decoded
+    let packet = file.next().unwrap()?;
+    assert_eq!("Unknown", packet.packet_name());
+    assert!(packet.records(file.header()).is_empty());
+
+    let packet = file.next().unwrap()?;
+    assert_eq!("QpsMultibeam", packet.packet_name());
+    match &packet.records(file.header())[..] {
+        [SonarDataRecord::Bathymetry(bathymetry)] => {
+            assert_eq!(
+                OffsetDateTime::from_unix_timestamp(1_600_000_000)? + Duration::milliseconds(250),
+                bathymetry.timestamp
+            );
+            assert_eq!(2, bathymetry.soundings.len());
+            let sounding = bathymetry.soundings[1];
+            assert_eq!(Some(0.03), sounding.travel_time);
+            assert_eq!(Some(21.5), sounding.depth);
+            assert_eq!(Some(11.0), sounding.across_track);
+            assert_eq!(Some(0.5), sounding.along_track);
+            assert_eq!(Some(-30.0), sounding.amplitude);
+        }
+        recs => panic!("Unexpected records {:?}", recs),
+    }
+    assert!(file.next().is_none());
+
+    // The bathymetry is indexed by a locker
+    let locker = OpenOptions::new().open(dir.clone())?;
+    let records: Vec<_> = locker
+        .query()
+        .kind(RecordKind::Bathymetry)
+        .records()
+        .collect();
+    match &records[..] {
+        [Ok(SonarDataRecord::Bathymetry(bathymetry))] => {
+            assert_eq!(Some(20.0), bathymetry.soundings[0].depth)
+        }
+        recs => panic!("Unexpected records {:?}", recs),
+    }
+
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[test]
+fn mixed_channels() -> Result<(), Box<dyn std::error::Error>> {
+    // Sub-bottom and bathymetry channels are both `Channel::Other`
+    let dir = locker_dir("xtf_mixed_channels");
+    let data = [
+        xtf_header(3, &[(0, 2, 4.0), (3, 2, 200.0)]),
+        xtf_ping(&[(0, 4, 75.0, &[1, 2]), (1, 200, 75.0, &[3, 4])]),
+    ]
+    .concat();
+    std::fs::write(dir.join("line.xtf"), data)?;
+
+    let locker = OpenOptions::new().open(dir.clone())?;
+    let pings: Vec<_> = locker.query().kind(RecordKind::Ping).records().collect();
+    match &pings[..] {
+        [Ok(SonarDataRecord::Ping(sub_bottom)), Ok(SonarDataRecord::Ping(bathy))] => {
+            assert_eq!(
+                (Channel::Other, 0),
+                (sub_bottom.channel, sub_bottom.subsystem)
+            );
+            assert_eq!(vec![1.0, 2.0], sub_bottom.data);
+            assert_eq!((Channel::Other, 1), (bathy.channel, bathy.subsystem));
+            assert_eq!(vec![3.0, 4.0], bathy.data);
+        }
+        recs => panic!("Unexpected records {:?}", recs),
+    }
+
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
+}